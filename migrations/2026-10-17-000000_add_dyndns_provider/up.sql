@@ -0,0 +1 @@
+ALTER TABLE dyndns ADD COLUMN provider TEXT NOT NULL DEFAULT 'dyndns2';
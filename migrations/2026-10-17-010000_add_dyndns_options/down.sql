@@ -0,0 +1 @@
+ALTER TABLE dyndns DROP COLUMN options;
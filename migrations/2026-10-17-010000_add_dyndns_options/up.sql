@@ -0,0 +1 @@
+ALTER TABLE dyndns ADD COLUMN options TEXT NOT NULL DEFAULT '{}';
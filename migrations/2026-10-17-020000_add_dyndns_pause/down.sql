@@ -0,0 +1,2 @@
+ALTER TABLE dyndns DROP COLUMN paused_at;
+ALTER TABLE dyndns DROP COLUMN paused_reason;
@@ -0,0 +1,2 @@
+ALTER TABLE dyndns ADD COLUMN paused_reason TEXT;
+ALTER TABLE dyndns ADD COLUMN paused_at TIMESTAMP;
@@ -0,0 +1,9 @@
+DROP INDEX idx_history_dyndns_id_version;
+
+ALTER TABLE history DROP COLUMN dyndns_id;
+
+ALTER TABLE dyndns ADD COLUMN sleep_interval INTEGER DEFAULT 10 NOT NULL CHECK(sleep_interval > 0);
+
+UPDATE dyndns SET sleep_interval = (SELECT sleep_interval FROM settings WHERE id = 1);
+
+DROP TABLE settings;
@@ -0,0 +1,35 @@
+CREATE TABLE settings (
+    id INTEGER PRIMARY KEY NOT NULL CHECK (id = 1),
+    sleep_interval INTEGER DEFAULT 10 NOT NULL CHECK(sleep_interval > 0)
+);
+
+INSERT INTO settings (id, sleep_interval)
+SELECT 1, COALESCE((SELECT sleep_interval FROM dyndns ORDER BY id LIMIT 1), 10);
+
+ALTER TABLE dyndns RENAME TO olddyndns;
+
+CREATE TABLE dyndns (
+    id INTEGER PRIMARY KEY NOT NULL,
+    server TEXT NOT NULL,
+    username TEXT NOT NULL,
+    password TEXT NOT NULL,
+    hostname TEXT NOT NULL,
+    ip INTEGER NOT NULL CHECK(ip in (1, 2, 3)),
+    interface TEXT NOT NULL,
+    provider TEXT NOT NULL DEFAULT 'dyndns2',
+    options TEXT NOT NULL DEFAULT '{}',
+    paused_reason TEXT,
+    paused_at TIMESTAMP
+);
+
+INSERT INTO dyndns (id, server, username, password, hostname, ip, interface, provider, options, paused_reason, paused_at)
+SELECT id, server, username, password, hostname, ip, interface, provider, options, paused_reason, paused_at FROM olddyndns;
+
+DROP TABLE olddyndns;
+
+ALTER TABLE history ADD COLUMN dyndns_id INTEGER;
+
+UPDATE history SET dyndns_id = (SELECT MIN(id) FROM dyndns);
+
+CREATE INDEX idx_history_dyndns_id_version
+    ON history (dyndns_id, version);
@@ -0,0 +1 @@
+DROP TABLE dyndns_status;
@@ -0,0 +1,10 @@
+CREATE TABLE dyndns_status (
+    dyndns_id INTEGER PRIMARY KEY NOT NULL,
+    last_checked_at TIMESTAMP,
+    last_attempt_at TIMESTAMP,
+    last_success_at TIMESTAMP,
+    last_result TEXT,
+    last_response TEXT,
+    consecutive_failures INTEGER NOT NULL DEFAULT 0,
+    next_run_at TIMESTAMP
+);
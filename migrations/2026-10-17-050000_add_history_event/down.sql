@@ -0,0 +1,3 @@
+ALTER TABLE dyndns DROP COLUMN max_age;
+
+ALTER TABLE history DROP COLUMN event;
@@ -0,0 +1,3 @@
+ALTER TABLE history ADD COLUMN event INTEGER NOT NULL DEFAULT 1 CHECK(event in (1, 2, 3));
+
+ALTER TABLE dyndns ADD COLUMN max_age INTEGER CHECK(max_age > 0);
@@ -0,0 +1,3 @@
+ALTER TABLE settings DROP COLUMN ipv6_lookup;
+
+ALTER TABLE settings DROP COLUMN ipv4_lookup;
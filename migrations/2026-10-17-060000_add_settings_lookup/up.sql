@@ -0,0 +1,3 @@
+ALTER TABLE settings ADD COLUMN ipv4_lookup TEXT NOT NULL DEFAULT '[{"url":"https://api-ipv4.ip.sb/ip","parser":"plain-text"}]';
+
+ALTER TABLE settings ADD COLUMN ipv6_lookup TEXT NOT NULL DEFAULT '[{"url":"https://api-ipv6.ip.sb/ip","parser":"plain-text"}]';
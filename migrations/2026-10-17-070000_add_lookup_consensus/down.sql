@@ -0,0 +1,7 @@
+DROP INDEX idx_events_created_at;
+
+DROP TABLE events;
+
+ALTER TABLE settings DROP COLUMN lookup_quorum;
+
+ALTER TABLE settings DROP COLUMN lookup_mode;
@@ -0,0 +1,12 @@
+ALTER TABLE settings ADD COLUMN lookup_mode TEXT NOT NULL DEFAULT 'fallback';
+
+ALTER TABLE settings ADD COLUMN lookup_quorum INTEGER NOT NULL DEFAULT 2 CHECK(lookup_quorum > 0);
+
+CREATE TABLE events (
+    id INTEGER PRIMARY KEY NOT NULL,
+    level TEXT NOT NULL,
+    message TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_events_created_at ON events (created_at);
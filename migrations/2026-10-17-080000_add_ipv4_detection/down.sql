@@ -0,0 +1 @@
+ALTER TABLE settings DROP COLUMN ipv4_detection;
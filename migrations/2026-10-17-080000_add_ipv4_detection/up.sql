@@ -0,0 +1 @@
+ALTER TABLE settings ADD COLUMN ipv4_detection TEXT NOT NULL DEFAULT 'external';
@@ -0,0 +1,3 @@
+ALTER TABLE settings DROP COLUMN reject_private_ipv4;
+
+ALTER TABLE dyndns_status DROP COLUMN ipv4_reachability;
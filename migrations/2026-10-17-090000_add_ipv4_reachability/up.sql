@@ -0,0 +1,3 @@
+ALTER TABLE dyndns_status ADD COLUMN ipv4_reachability TEXT;
+
+ALTER TABLE settings ADD COLUMN reject_private_ipv4 BOOLEAN NOT NULL DEFAULT 0;
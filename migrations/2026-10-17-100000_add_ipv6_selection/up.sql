@@ -0,0 +1 @@
+ALTER TABLE settings ADD COLUMN ipv6_selection TEXT NOT NULL DEFAULT '{"policy":"stable"}';
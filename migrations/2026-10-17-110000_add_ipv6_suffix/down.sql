@@ -0,0 +1,5 @@
+ALTER TABLE history DROP COLUMN new_prefix;
+ALTER TABLE history DROP COLUMN old_prefix;
+
+ALTER TABLE dyndns DROP COLUMN ipv6_prefix_length;
+ALTER TABLE dyndns DROP COLUMN ipv6_suffix;
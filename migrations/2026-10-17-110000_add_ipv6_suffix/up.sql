@@ -0,0 +1,5 @@
+ALTER TABLE dyndns ADD COLUMN ipv6_suffix TEXT;
+ALTER TABLE dyndns ADD COLUMN ipv6_prefix_length INTEGER;
+
+ALTER TABLE history ADD COLUMN old_prefix TEXT;
+ALTER TABLE history ADD COLUMN new_prefix TEXT;
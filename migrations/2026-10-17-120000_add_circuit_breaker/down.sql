@@ -0,0 +1,3 @@
+ALTER TABLE settings DROP COLUMN failure_threshold;
+
+ALTER TABLE dyndns_status DROP COLUMN circuit_opened_at;
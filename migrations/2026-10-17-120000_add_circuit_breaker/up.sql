@@ -0,0 +1,3 @@
+ALTER TABLE dyndns_status ADD COLUMN circuit_opened_at TIMESTAMP;
+
+ALTER TABLE settings ADD COLUMN failure_threshold INTEGER NOT NULL DEFAULT 5;
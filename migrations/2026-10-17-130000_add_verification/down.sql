@@ -0,0 +1,3 @@
+ALTER TABLE history DROP COLUMN verified;
+
+ALTER TABLE dyndns DROP COLUMN verify;
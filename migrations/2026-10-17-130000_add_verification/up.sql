@@ -0,0 +1,3 @@
+ALTER TABLE dyndns ADD COLUMN verify TEXT;
+
+ALTER TABLE history ADD COLUMN verified BOOLEAN;
@@ -0,0 +1 @@
+ALTER TABLE dyndns ADD COLUMN compare_dns TEXT;
@@ -1,49 +1,114 @@
 use axum::{
     Json, Router,
-    extract::{FromRequest, Request, State},
+    extract::{FromRequest, Path, Query, Request, State},
+    http::StatusCode,
     response::{IntoResponse, Response},
-    routing::{get, post, put},
+    routing::{get, post},
 };
+use serde::Deserialize;
 use validator::Validate;
 
-use crate::{AppState, DbPool, Error, db::DynDNS};
+use crate::{
+    AppState, DbPool, Error,
+    db::{DynDNS, DynDnsRes, Settings},
+    dyndns::{CycleReport, DryRunReport, SchedulerHandle, clear_records, dry_run},
+};
 
 pub fn routes() -> Router<AppState> {
     Router::new()
-        .route("/", get(get_dyndns))
-        .route("/", post(create_dyndns))
-        .route("/", put(update_dyndns))
+        .route("/", get(list_dyndns).post(create_dyndns))
+        .route("/update", post(update_now))
+        .route("/force-update", post(force_update))
+        .route("/test", post(test_dyndns))
+        .route(
+            "/{id}",
+            get(get_dyndns).put(update_dyndns).delete(delete_dyndns),
+        )
+        .route("/{id}/clear", post(clear_dyndns))
 }
 
-async fn get_dyndns(State(pool): State<DbPool>) -> Result<Json<DynDNS>, Error> {
+async fn list_dyndns(State(pool): State<DbPool>) -> Result<Json<Vec<DynDnsRes>>, Error> {
     let conn = pool.get().await?;
-    match DynDNS::get_option(&conn).await? {
-        Some(res) => Ok(Json(res)),
-        None => Err(Error::dyn_dns_not_configured()),
-    }
+    let res = DynDNS::list(&conn).await?;
+    Ok(Json(res.into_iter().map(DynDnsRes::from).collect()))
+}
+
+async fn get_dyndns(
+    State(pool): State<DbPool>,
+    Path(id): Path<i32>,
+) -> Result<Json<DynDnsRes>, Error> {
+    let conn = pool.get().await?;
+    let res = DynDNS::find(&conn, id).await?;
+    Ok(Json(res.into()))
 }
 
 async fn create_dyndns(
-    State(state): State<AppState>,
+    State(pool): State<DbPool>,
     dyndns: DynDNS,
-) -> Result<Json<DynDNS>, Error> {
-    let conn = state.pool.get().await?;
-    let res = match DynDNS::get_option(&conn).await? {
-        Some(res) => res,
-        None => DynDNS::create(&conn, dyndns).await?,
-    };
-    Ok(Json(res))
+) -> Result<(StatusCode, Json<DynDnsRes>), Error> {
+    let conn = pool.get().await?;
+    let res = DynDNS::create(&conn, dyndns).await?;
+    Ok((StatusCode::CREATED, Json(res.into())))
 }
 
 async fn update_dyndns(
-    State(state): State<AppState>,
+    State(pool): State<DbPool>,
+    Path(id): Path<i32>,
+    dyndns: DynDNS,
+) -> Result<Json<DynDnsRes>, Error> {
+    let conn = pool.get().await?;
+    let res = DynDNS::update(&conn, id, dyndns).await?;
+    Ok(Json(res.into()))
+}
+
+async fn delete_dyndns(
+    State(pool): State<DbPool>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, Error> {
+    let conn = pool.get().await?;
+    DynDNS::delete(&conn, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn clear_dyndns(
+    State(pool): State<DbPool>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, Error> {
+    let record = {
+        let conn = pool.get().await?;
+        DynDNS::find(&conn, id).await?
+    };
+    clear_records(&record.config).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn update_now(State(scheduler): State<SchedulerHandle>) -> Result<Json<CycleReport>, Error> {
+    Ok(Json(scheduler.trigger(false).await?))
+}
+
+async fn force_update(
+    State(scheduler): State<SchedulerHandle>,
+) -> Result<Json<CycleReport>, Error> {
+    Ok(Json(scheduler.trigger(true).await?))
+}
+
+#[derive(Deserialize)]
+struct TestQuery {
+    /// Sends a real update instead of a read-only check.
+    #[serde(default)]
+    update: bool,
+}
+
+async fn test_dyndns(
+    State(pool): State<DbPool>,
+    Query(query): Query<TestQuery>,
     dyndns: DynDNS,
-) -> Result<Json<DynDNS>, Error> {
-    let conn = state.pool.get().await?;
-    let interval = dyndns.sleep_interval.get();
-    let res = DynDNS::update(&conn, dyndns).await?;
-    state.interval_tx.send_replace(interval);
-    Ok(Json(res))
+) -> Result<Json<DryRunReport>, Error> {
+    let settings = {
+        let conn = pool.get().await?;
+        Settings::get(&conn).await?
+    };
+    Ok(Json(dry_run(&dyndns, &settings, query.update).await))
 }
 
 impl<S> FromRequest<S> for DynDNS
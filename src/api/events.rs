@@ -0,0 +1,23 @@
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    routing::get,
+};
+
+use crate::{
+    AppState, DbPool, Error,
+    db::{Event, EventRes, Pagination},
+};
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/", get(events))
+}
+
+async fn events(
+    State(pool): State<DbPool>,
+    Query(pagination): Query<Pagination>,
+) -> Result<Json<EventRes>, Error> {
+    let conn = pool.get().await?;
+    let (events, total) = Event::paginate(&conn, pagination.page, pagination.per_page).await?;
+    Ok(Json(EventRes::new(total, events)))
+}
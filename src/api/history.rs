@@ -10,7 +10,7 @@ use serde::{Deserialize, Deserializer};
 
 use crate::{
     AppState, DbPool, Error,
-    db::{BoxHistoryOrder, History, HistoryIpVersion, HistoryRes, history},
+    db::{BoxHistoryOrder, History, HistoryIpVersion, HistoryRes, Pagination, history},
 };
 
 pub fn routes() -> Router<AppState> {
@@ -19,22 +19,6 @@ pub fn routes() -> Router<AppState> {
         .route("/current", get(current))
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(default)]
-struct Pagination {
-    page: usize,
-    per_page: i64,
-}
-
-impl Default for Pagination {
-    fn default() -> Self {
-        Self {
-            page: 1,
-            per_page: 10,
-        }
-    }
-}
-
 #[derive(Deserialize, Debug)]
 #[serde(default)]
 struct SortBy {
@@ -168,6 +152,7 @@ async fn history(
 #[derive(Deserialize)]
 struct Current {
     version: HistoryIpVersion,
+    dyndns_id: Option<i32>,
 }
 
 async fn current(
@@ -175,6 +160,6 @@ async fn current(
     Query(query): Query<Current>,
 ) -> Result<Json<Option<History>>, Error> {
     let conn = pool.get().await?;
-    let history = History::get_current(&conn, query.version).await?;
+    let history = History::get_current(&conn, query.version, query.dyndns_id).await?;
     Ok(Json(history))
 }
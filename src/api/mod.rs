@@ -5,15 +5,21 @@ use crate::{AppState, middleware::auth::AuthLayer};
 
 mod auth;
 mod dyndns;
+mod events;
 mod history;
 mod interfaces;
+mod settings;
+mod status;
 
 pub fn routes(state: &AppState) -> Router<AppState> {
     let auth_layer = AuthLayer::new(state.auth.clone());
     let protected_routes = Router::new()
         .nest("/dyndns", dyndns::routes())
+        .nest("/events", events::routes())
         .nest("/history", history::routes())
         .nest("/interfaces", interfaces::routes())
+        .nest("/settings", settings::routes())
+        .nest("/status", status::routes())
         .route_layer(auth_layer);
 
     Router::new()
@@ -0,0 +1,28 @@
+use axum::{Json, Router, extract::State, routing::get};
+
+use validator::Validate;
+
+use crate::{AppState, DbPool, Error, db::Settings};
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/", get(get_settings).put(update_settings))
+}
+
+async fn get_settings(State(pool): State<DbPool>) -> Result<Json<Settings>, Error> {
+    let conn = pool.get().await?;
+    Ok(Json(Settings::get(&conn).await?))
+}
+
+async fn update_settings(
+    State(state): State<AppState>,
+    Json(settings): Json<Settings>,
+) -> Result<Json<Settings>, Error> {
+    if let Err(e) = settings.validate() {
+        return Err(Error::validation_failed(e.to_string()));
+    }
+    let conn = state.pool.get().await?;
+    let interval = settings.sleep_interval.get();
+    let res = Settings::update(&conn, settings).await?;
+    state.interval_tx.send_replace(interval);
+    Ok(Json(res))
+}
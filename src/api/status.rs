@@ -0,0 +1,15 @@
+use axum::{Json, Router, extract::State, routing::get};
+
+use crate::{
+    AppState, DbPool, Error,
+    db::{DynDnsStatus, DynDnsStatusRes},
+};
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/", get(get_status))
+}
+
+async fn get_status(State(pool): State<DbPool>) -> Result<Json<Vec<DynDnsStatusRes>>, Error> {
+    let conn = pool.get().await?;
+    Ok(Json(DynDnsStatus::list(&conn).await?))
+}
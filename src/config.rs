@@ -10,21 +10,16 @@ use serde::Deserialize;
 
 const PREFIX: &str = "DYNDNS";
 
-pub static CONFIG: Lazy<Config> = Lazy::new(|| init_config());
+pub static CONFIG: Lazy<Config> = Lazy::new(init_config);
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub enum LogStyle {
+    #[default]
     Auto,
     Always,
     Never,
 }
 
-impl Default for LogStyle {
-    fn default() -> Self {
-        Self::Auto
-    }
-}
-
 impl LogStyle {
     pub fn is_color(&self) -> bool {
         match self {
@@ -13,10 +13,10 @@ pub fn run_migrations() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
     // See the documentation for `MigrationHarness` for
     // all available methods.
     let path = Path::new(&CONFIG.database_url);
-    if let Some(path) = path.parent() {
-        if !path.exists() {
-            create_dir_all(path)?;
-        }
+    if let Some(path) = path.parent()
+        && !path.exists()
+    {
+        create_dir_all(path)?;
     }
     let mut connection = diesel::sqlite::SqliteConnection::establish(&CONFIG.database_url)?;
     connection.run_pending_migrations(MIGRATIONS)?;
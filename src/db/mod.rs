@@ -5,8 +5,12 @@ mod schema;
 
 pub use migration::run_migrations;
 pub use models::{
-    AuthSecretRecord, BoxHistoryOrder, DynDNS, History, HistoryIpVersion, HistoryRes, IpVersion,
-    RefreshTokenRecord,
+    AttemptResult, AuthSecretRecord, BoxHistoryOrder, DnsLookupTarget, DnsRecordType, DynDNS,
+    DynDnsAttempt, DynDnsRecord, DynDnsRes, DynDnsStatus, DynDnsStatusRes, Event, EventLevel,
+    EventRes, History, HistoryEvent, HistoryIpVersion, HistoryRes, IpVersion, Ipv4Detection,
+    Ipv4Reachability, Ipv6Selection, LookupMode, LookupParserKind, LookupSource, LookupSources,
+    ProviderKind, ProviderOptions, RecordLookup, RefreshTokenRecord, Settings, TsigAlgorithm,
+    WebhookOptions,
 };
-pub use pagination::Paginate;
-pub use schema::{auth_secrets, dyndns, history, refresh_tokens};
+pub use pagination::{Paginate, Pagination};
+pub use schema::{auth_secrets, dyndns, dyndns_status, events, history, refresh_tokens, settings};
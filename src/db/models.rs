@@ -1,9 +1,11 @@
 use std::{
     borrow::Cow,
+    collections::BTreeMap,
     net::{Ipv4Addr, Ipv6Addr},
 };
 
-use axum::http::Uri;
+use axum::http::{Method, Uri};
+use base64::{Engine, engine::general_purpose::STANDARD};
 use chrono::{NaiveDateTime, Utc};
 
 use diesel::{
@@ -12,13 +14,15 @@ use diesel::{
     expression::expression_types::NotSelectable,
     prelude::*,
     serialize::{IsNull, Output, ToSql},
-    sql_types::{BigInt, Integer},
+    sql_types::{BigInt, Integer, Text},
     sqlite::{Sqlite, SqliteValue},
 };
 use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
 use validator::{Validate, ValidationError};
 
-use super::{Paginate, auth_secrets, dyndns, history, refresh_tokens};
+use super::{
+    Paginate, auth_secrets, dyndns, dyndns_status, events, history, refresh_tokens, settings,
+};
 use crate::{DbConn, Error, error::SleepIntervalError, util::get_interfaces};
 
 #[repr(i32)]
@@ -27,7 +31,7 @@ use crate::{DbConn, Error, error::SleepIntervalError, util::get_interfaces};
 pub enum IpVersion {
     V4 = 1,
     V6 = 2,
-    ALL = 3,
+    All = 3,
 }
 
 impl ToSql<Integer, diesel::sqlite::Sqlite> for IpVersion {
@@ -45,7 +49,7 @@ impl FromSql<Integer, diesel::sqlite::Sqlite> for IpVersion {
         match i32::from_sql(bytes)? {
             1 => Ok(Self::V4),
             2 => Ok(Self::V6),
-            3 => Ok(Self::ALL),
+            3 => Ok(Self::All),
             x => Err(format!("Unrecognized variant {}", x).into()),
         }
     }
@@ -147,7 +151,7 @@ impl<'de> Deserialize<'de> for IpVersion {
         match v {
             1 => Ok(Self::V4),
             2 => Ok(Self::V6),
-            3 => Ok(Self::ALL),
+            3 => Ok(Self::All),
             _ => Err(de::Error::unknown_field(
                 v.to_string().as_str(),
                 &["1", "2", "3"],
@@ -156,23 +160,201 @@ impl<'de> Deserialize<'de> for IpVersion {
     }
 }
 
+#[derive(Debug, Default, FromSqlRow, AsExpression, Clone, Copy, PartialEq, Eq)]
+#[diesel(sql_type = Text)]
+pub enum ProviderKind {
+    #[default]
+    Dyndns2,
+    Cloudflare,
+    Rfc2136,
+    Webhook,
+    Duckdns,
+}
+
+impl ProviderKind {
+    const VARIANTS: &'static [&'static str] =
+        &["dyndns2", "cloudflare", "rfc2136", "webhook", "duckdns"];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Dyndns2 => "dyndns2",
+            Self::Cloudflare => "cloudflare",
+            Self::Rfc2136 => "rfc2136",
+            Self::Webhook => "webhook",
+            Self::Duckdns => "duckdns",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "dyndns2" => Some(Self::Dyndns2),
+            "cloudflare" => Some(Self::Cloudflare),
+            "rfc2136" => Some(Self::Rfc2136),
+            "webhook" => Some(Self::Webhook),
+            "duckdns" => Some(Self::Duckdns),
+            _ => None,
+        }
+    }
+}
+
+impl ToSql<Text, Sqlite> for ProviderKind {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for ProviderKind {
+    fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        Self::parse(&value).ok_or_else(|| format!("Unrecognized provider {}", value).into())
+    }
+}
+
+impl Serialize for ProviderKind {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ProviderKind {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?.to_lowercase();
+        Self::parse(&s).ok_or_else(|| de::Error::unknown_variant(&s, Self::VARIANTS))
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize, FromSqlRow, AsExpression)]
+#[diesel(sql_type = Text)]
+#[serde(default)]
+pub struct ProviderOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxied: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tsig_algorithm: Option<TsigAlgorithm>,
+    /// Publish every selected IPv6 address as its own AAAA record instead of one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publish_all_ipv6: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<WebhookOptions>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TsigAlgorithm {
+    #[default]
+    HmacSha256,
+    HmacSha512,
+}
+
+impl TsigAlgorithm {
+    pub fn dns_name(&self) -> &'static str {
+        match self {
+            Self::HmacSha256 => "hmac-sha256.",
+            Self::HmacSha512 => "hmac-sha512.",
+        }
+    }
+}
+
+/// Request sent by the webhook provider. The method, url, header values and
+/// body are templates; see the webhook provider for the variables.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WebhookOptions {
+    #[serde(default = "WebhookOptions::method")]
+    pub method: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub success: WebhookSuccess,
+}
+
+impl WebhookOptions {
+    fn method() -> String {
+        "GET".to_string()
+    }
+}
+
+/// When a webhook response counts as a successful update.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct WebhookSuccess {
+    /// Accepted status codes, any 2xx when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub status: Vec<u16>,
+    /// Pattern the response body has to match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+impl ToSql<Text, Sqlite> for ProviderOptions {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(serde_json::to_string(self)?);
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for ProviderOptions {
+    fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        Ok(serde_json::from_str(&value)?)
+    }
+}
+
 #[derive(
-    Debug, Deserialize, Serialize, Selectable, Queryable, Insertable, AsChangeset, Validate,
+    Debug, Clone, Deserialize, Serialize, Selectable, Queryable, Insertable, AsChangeset, Validate,
 )]
 #[diesel(table_name = dyndns)]
+#[validate(schema(function = "validate_provider"))]
 pub struct DynDNS {
     #[validate(custom(function = "validate_host"))]
     pub server: String,
-    #[validate(length(min = 1))]
     pub username: String,
     #[validate(length(min = 1))]
     pub password: String,
-    #[validate(custom(function = "validate_host"))]
+    #[validate(custom(function = "validate_hostnames"))]
     pub hostname: String,
     pub ip: IpVersion,
     #[validate(length(min = 1), custom(function = "validate_interface"))]
     pub interface: String,
-    pub sleep_interval: SleepInterval,
+    #[serde(default)]
+    pub provider: ProviderKind,
+    #[serde(default)]
+    pub options: ProviderOptions,
+    /// Seconds after which the current address is pushed again even if unchanged.
+    #[serde(default)]
+    #[validate(range(min = 1))]
+    pub max_age: Option<i64>,
+    /// Fixed interface identifier published under the interface's current prefix.
+    #[serde(default)]
+    #[validate(custom(function = "validate_ipv6_suffix"))]
+    pub ipv6_suffix: Option<String>,
+    /// Length of the prefix taken from the interface, 64 when unset.
+    #[serde(default)]
+    #[validate(range(min = 1, max = 127))]
+    pub ipv6_prefix_length: Option<i32>,
+    /// Checks that the published records resolve after an update.
+    #[serde(default)]
+    #[validate(custom(function = "validate_record_lookup"))]
+    pub verify: Option<RecordLookup>,
+    /// Decides whether to update from what the hostname resolves to instead of
+    /// the history, which then only serves as an audit trail.
+    #[serde(default)]
+    #[validate(custom(function = "validate_record_lookup"))]
+    pub compare_dns: Option<RecordLookup>,
 }
 
 fn validate_interface(interface: &str) -> Result<(), ValidationError> {
@@ -180,7 +362,7 @@ fn validate_interface(interface: &str) -> Result<(), ValidationError> {
     let interfaces = get_interfaces();
     match interfaces {
         Ok(interfaces) => {
-            if interfaces.get(interface).is_none() {
+            if !interfaces.contains(interface) {
                 error.message = Some(Cow::Owned(format!(
                     "unknown field `{}`, expected {:?}",
                     interface, interfaces
@@ -196,6 +378,143 @@ fn validate_interface(interface: &str) -> Result<(), ValidationError> {
     Ok(())
 }
 
+fn validate_provider(dyndns: &DynDNS) -> Result<(), ValidationError> {
+    let mut error = ValidationError::new("provider");
+    match dyndns.provider {
+        ProviderKind::Dyndns2 => {
+            if dyndns.username.is_empty() {
+                error.message = Some(Cow::Borrowed("dyndns2 requires a username"));
+                return Err(error);
+            }
+        }
+        ProviderKind::Cloudflare => {
+            if !has_valid_api_url(&dyndns.options) {
+                error.message = Some(Cow::Borrowed("api_url must be an absolute url"));
+                return Err(error);
+            }
+        }
+        ProviderKind::Rfc2136 => {
+            if dyndns.username.is_empty() {
+                error.message = Some(Cow::Borrowed("rfc2136 requires a tsig key name"));
+                return Err(error);
+            }
+            if STANDARD.decode(&dyndns.password).is_err() {
+                error.message = Some(Cow::Borrowed("tsig secret must be base64 encoded"));
+                return Err(error);
+            }
+        }
+        ProviderKind::Duckdns => {
+            if !has_valid_api_url(&dyndns.options) {
+                error.message = Some(Cow::Borrowed("api_url must be an absolute url"));
+                return Err(error);
+            }
+            if let Some(hostname) = dyndns.hostnames().find(|hostname| {
+                hostname
+                    .strip_suffix(".duckdns.org")
+                    .unwrap_or(hostname)
+                    .contains('.')
+            }) {
+                error.message = Some(Cow::Owned(format!(
+                    "{} is not a duckdns.org subdomain",
+                    hostname
+                )));
+                return Err(error);
+            }
+            // DuckDNS takes a single IPv6 address, and verification expects
+            // every published one to resolve.
+            if dyndns.options.publish_all_ipv6 == Some(true) {
+                error.message = Some(Cow::Borrowed(
+                    "duckdns accepts a single ipv6 address, publish_all_ipv6 is not supported",
+                ));
+                return Err(error);
+            }
+        }
+        ProviderKind::Webhook => {
+            let Some(webhook) = dyndns.options.webhook.as_ref() else {
+                error.message = Some(Cow::Borrowed("webhook requires options.webhook"));
+                return Err(error);
+            };
+            if !webhook.method.contains('{')
+                && Method::from_bytes(webhook.method.to_uppercase().as_bytes()).is_err()
+            {
+                error.message = Some(Cow::Owned(format!(
+                    "invalid webhook method {}",
+                    webhook.method
+                )));
+                return Err(error);
+            }
+            if let Some(pattern) = webhook.success.body.as_deref()
+                && let Err(err) = regex::Regex::new(pattern)
+            {
+                error.message = Some(Cow::Owned(format!(
+                    "invalid webhook success regex {}: {}",
+                    pattern, err
+                )));
+                return Err(error);
+            }
+            if let Some(status) = webhook
+                .success
+                .status
+                .iter()
+                .find(|status| !(100..600).contains(*status))
+            {
+                error.message = Some(Cow::Owned(format!(
+                    "invalid webhook success status {}",
+                    status
+                )));
+                return Err(error);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn has_valid_api_url(options: &ProviderOptions) -> bool {
+    options.api_url.as_deref().is_none_or(|api_url| {
+        api_url
+            .parse::<Uri>()
+            .is_ok_and(|url| url.scheme().is_some())
+    })
+}
+
+fn validate_ipv6_suffix(suffix: &str) -> Result<(), ValidationError> {
+    if suffix.parse::<Ipv6Addr>().is_err() {
+        let mut error = ValidationError::new("ipv6_suffix");
+        error.message = Some(Cow::Borrowed(
+            "ipv6_suffix must be an ipv6 address like ::1234",
+        ));
+        return Err(error);
+    }
+    Ok(())
+}
+
+fn validate_record_lookup(lookup: &RecordLookup) -> Result<(), ValidationError> {
+    match lookup {
+        RecordLookup::Authoritative => Ok(()),
+        RecordLookup::Resolver { server } => validate_host(server),
+    }
+}
+
+fn validate_hostnames(hostnames: &str) -> Result<(), ValidationError> {
+    let mut count = 0;
+    for hostname in hostnames.split(',').map(str::trim) {
+        validate_host(hostname)?;
+        count += 1;
+    }
+    if count == 0
+        || hostnames
+            .split(',')
+            .any(|hostname| hostname.trim().is_empty())
+    {
+        let mut error = ValidationError::new("hostname");
+        error.message = Some(Cow::Borrowed(
+            "hostname list must not contain empty entries",
+        ));
+        return Err(error);
+    }
+    Ok(())
+}
+
 fn validate_host(host: &str) -> Result<(), ValidationError> {
     let mut error = ValidationError::new("host");
     let url = host.parse::<Uri>();
@@ -214,60 +533,844 @@ fn validate_host(host: &str) -> Result<(), ValidationError> {
 }
 
 impl DynDNS {
-    pub async fn get(conn: &DbConn) -> Result<DynDNS, Error> {
-        conn.interact(|conn| dyndns::table.select(DynDNS::as_select()).first(conn))
-            .await?
-            .map_err(|e| e.into())
+    pub fn hostnames(&self) -> impl Iterator<Item = &str> {
+        self.hostname.split(',').map(str::trim)
     }
 
-    pub async fn get_option(conn: &DbConn) -> Result<Option<DynDNS>, Error> {
+    pub async fn list(conn: &DbConn) -> Result<Vec<DynDnsRecord>, Error> {
         conn.interact(|conn| {
             dyndns::table
-                .select(DynDNS::as_select())
+                .select(DynDnsRecord::as_select())
+                .order(dyndns::id.asc())
+                .load(conn)
+        })
+        .await?
+        .map_err(|e| e.into())
+    }
+
+    pub async fn find(conn: &DbConn, id: i32) -> Result<DynDnsRecord, Error> {
+        conn.interact(move |conn| {
+            dyndns::table
+                .find(id)
+                .select(DynDnsRecord::as_select())
                 .first(conn)
-                .optional()
         })
         .await?
         .map_err(|e| e.into())
     }
 
-    pub async fn create(conn: &DbConn, dyndns: DynDNS) -> Result<DynDNS, Error> {
+    pub async fn create(conn: &DbConn, dyndns: DynDNS) -> Result<DynDnsRecord, Error> {
         conn.interact(|conn| {
-            diesel::replace_into(dyndns::table)
+            diesel::insert_into(dyndns::table)
                 .values(dyndns)
-                .returning(DynDNS::as_returning())
+                .returning(DynDnsRecord::as_returning())
                 .get_result(conn)
         })
         .await?
         .map_err(|e| e.into())
     }
 
-    pub async fn update(conn: &DbConn, dyndns: DynDNS) -> Result<DynDNS, Error> {
-        conn.interact(|conn| {
-            diesel::update(dyndns::table)
-                .filter(dyndns::id.eq(1))
-                .set(dyndns)
-                .returning(DynDNS::as_returning())
+    pub async fn update(conn: &DbConn, id: i32, dyndns: DynDNS) -> Result<DynDnsRecord, Error> {
+        conn.interact(move |conn| {
+            diesel::update(dyndns::table.find(id))
+                .set((
+                    dyndns,
+                    dyndns::paused_reason.eq(None::<String>),
+                    dyndns::paused_at.eq(None::<NaiveDateTime>),
+                ))
+                .returning(DynDnsRecord::as_returning())
                 .get_result(conn)
         })
         .await?
         .map_err(|e| e.into())
     }
 
-    pub async fn get_sleep_interval(conn: &DbConn) -> Result<SleepInterval, Error> {
-        conn.interact(|conn| dyndns::table.select(dyndns::sleep_interval).first(conn))
-            .await?
-            .map_err(|e| e.into())
+    pub async fn delete(conn: &DbConn, id: i32) -> Result<(), Error> {
+        let deleted = conn
+            .interact(move |conn| {
+                conn.transaction(|conn| {
+                    diesel::delete(dyndns_status::table.find(id)).execute(conn)?;
+                    diesel::delete(dyndns::table.find(id)).execute(conn)
+                })
+            })
+            .await??;
+        if deleted == 0 {
+            return Err(diesel::result::Error::NotFound.into());
+        }
+        Ok(())
+    }
+
+    pub async fn pause(conn: &DbConn, id: i32, reason: &str) -> Result<(), Error> {
+        let reason = reason.to_owned();
+        conn.interact(move |conn| {
+            diesel::update(dyndns::table.find(id))
+                .set((
+                    dyndns::paused_reason.eq(Some(reason)),
+                    dyndns::paused_at.eq(Some(Utc::now().naive_utc())),
+                ))
+                .execute(conn)
+        })
+        .await??;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = dyndns)]
+pub struct DynDnsRecord {
+    pub id: i32,
+    #[diesel(embed)]
+    pub config: DynDNS,
+    paused_reason: Option<String>,
+    paused_at: Option<NaiveDateTime>,
+}
+
+impl DynDnsRecord {
+    pub fn paused(&self) -> Option<DynDnsPause> {
+        match (&self.paused_reason, self.paused_at) {
+            (Some(reason), Some(paused_at)) => Some(DynDnsPause {
+                reason: reason.clone(),
+                paused_at,
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DynDnsPause {
+    pub reason: String,
+    pub paused_at: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+pub struct DynDnsRes {
+    id: i32,
+    #[serde(flatten)]
+    dyndns: DynDNS,
+    paused: Option<DynDnsPause>,
+}
+
+impl From<DynDnsRecord> for DynDnsRes {
+    fn from(record: DynDnsRecord) -> Self {
+        let paused = record.paused();
+        Self {
+            id: record.id,
+            dyndns: record.config,
+            paused,
+        }
+    }
+}
+
+#[derive(Debug, FromSqlRow, AsExpression, Clone, Copy, PartialEq, Eq)]
+#[diesel(sql_type = Text)]
+pub enum AttemptResult {
+    Updated,
+    Unchanged,
+    Failed,
+    RetryLater,
+    Fatal,
+    Error,
+}
+
+impl AttemptResult {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Updated => "updated",
+            Self::Unchanged => "unchanged",
+            Self::Failed => "failed",
+            Self::RetryLater => "retry_later",
+            Self::Fatal => "fatal",
+            Self::Error => "error",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "updated" => Some(Self::Updated),
+            "unchanged" => Some(Self::Unchanged),
+            "failed" => Some(Self::Failed),
+            "retry_later" => Some(Self::RetryLater),
+            "fatal" => Some(Self::Fatal),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Updated | Self::Unchanged)
+    }
+}
+
+impl ToSql<Text, Sqlite> for AttemptResult {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for AttemptResult {
+    fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        Self::parse(&value).ok_or_else(|| format!("Unrecognized attempt result {}", value).into())
+    }
+}
+
+impl Serialize for AttemptResult {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// Whether the published IPv4 address can be reached from the internet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, FromSqlRow, AsExpression)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub enum Ipv4Reachability {
+    /// The public address is assigned to the interface.
+    Direct,
+    /// The interface sits behind a NAT with a different or private address.
+    Nat,
+    /// The interface or the reported address is in the shared 100.64.0.0/10 range.
+    Cgnat,
+}
+
+impl Ipv4Reachability {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Direct => "direct",
+            Self::Nat => "nat",
+            Self::Cgnat => "cgnat",
+        }
+    }
+}
+
+impl ToSql<Text, Sqlite> for Ipv4Reachability {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for Ipv4Reachability {
+    fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        match value.as_str() {
+            "direct" => Ok(Self::Direct),
+            "nat" => Ok(Self::Nat),
+            "cgnat" => Ok(Self::Cgnat),
+            _ => Err(format!("Unrecognized ipv4 reachability {}", value).into()),
+        }
+    }
+}
+
+/// Outcome of a single update attempt against a provider.
+pub struct DynDnsAttempt {
+    pub result: AttemptResult,
+    pub response: Option<String>,
+    pub next_run_at: Option<NaiveDateTime>,
+    /// Whether the target's circuit breaker is open after this attempt.
+    pub circuit_open: bool,
+    /// Whether the provider was called. Failures before that, such as a
+    /// failed detection, do not count towards the circuit breaker.
+    pub provider_called: bool,
+}
+
+#[derive(Debug, Default, Serialize, Queryable, Selectable, Insertable)]
+#[diesel(table_name = dyndns_status)]
+pub struct DynDnsStatus {
+    #[serde(skip)]
+    pub dyndns_id: i32,
+    pub last_checked_at: Option<NaiveDateTime>,
+    pub last_attempt_at: Option<NaiveDateTime>,
+    pub last_success_at: Option<NaiveDateTime>,
+    pub last_result: Option<AttemptResult>,
+    pub last_response: Option<String>,
+    pub consecutive_failures: i32,
+    pub next_run_at: Option<NaiveDateTime>,
+    pub ipv4_reachability: Option<Ipv4Reachability>,
+    /// Set while repeated failures keep the target's circuit breaker open.
+    pub circuit_opened_at: Option<NaiveDateTime>,
+}
+
+impl DynDnsStatus {
+    pub async fn list(conn: &DbConn) -> Result<Vec<DynDnsStatusRes>, Error> {
+        let rows = conn
+            .interact(|conn| {
+                dyndns::table
+                    .left_join(dyndns_status::table.on(dyndns_status::dyndns_id.eq(dyndns::id)))
+                    .select((
+                        DynDnsRecord::as_select(),
+                        Option::<DynDnsStatus>::as_select(),
+                    ))
+                    .order(dyndns::id.asc())
+                    .load::<(DynDnsRecord, Option<DynDnsStatus>)>(conn)
+            })
+            .await??;
+        Ok(rows
+            .into_iter()
+            .map(|(record, status)| DynDnsStatusRes::new(record, status.unwrap_or_default()))
+            .collect())
+    }
+
+    pub async fn load_all(conn: &DbConn) -> Result<Vec<DynDnsStatus>, Error> {
+        conn.interact(|conn| {
+            dyndns_status::table
+                .select(DynDnsStatus::as_select())
+                .load(conn)
+        })
+        .await?
+        .map_err(|e| e.into())
+    }
+
+    /// Marks the target as checked without contacting its provider.
+    pub async fn record_check(
+        conn: &DbConn,
+        dyndns_id: i32,
+        next_run_at: Option<NaiveDateTime>,
+        reachability: Option<Ipv4Reachability>,
+    ) -> Result<(), Error> {
+        let now = Utc::now().naive_utc();
+        conn.interact(move |conn| {
+            diesel::insert_into(dyndns_status::table)
+                .values((
+                    dyndns_status::dyndns_id.eq(dyndns_id),
+                    dyndns_status::last_checked_at.eq(Some(now)),
+                    dyndns_status::next_run_at.eq(next_run_at),
+                    dyndns_status::ipv4_reachability.eq(reachability),
+                ))
+                .on_conflict(dyndns_status::dyndns_id)
+                .do_update()
+                .set((
+                    dyndns_status::last_checked_at.eq(Some(now)),
+                    dyndns_status::next_run_at.eq(next_run_at),
+                    dyndns_status::ipv4_reachability.eq(reachability),
+                ))
+                .execute(conn)
+        })
+        .await??;
+        Ok(())
+    }
+
+    pub async fn record_attempt(
+        conn: &DbConn,
+        dyndns_id: i32,
+        attempt: DynDnsAttempt,
+        reachability: Option<Ipv4Reachability>,
+    ) -> Result<(), Error> {
+        let now = Utc::now().naive_utc();
+        conn.interact(move |conn| {
+            conn.transaction(|conn| {
+                let previous = dyndns_status::table
+                    .find(dyndns_id)
+                    .select(DynDnsStatus::as_select())
+                    .first(conn)
+                    .optional()?
+                    .unwrap_or_default();
+                let success = attempt.result.is_success();
+                let status = DynDnsStatus {
+                    dyndns_id,
+                    last_checked_at: Some(now),
+                    last_attempt_at: Some(now),
+                    last_success_at: if success {
+                        Some(now)
+                    } else {
+                        previous.last_success_at
+                    },
+                    last_result: Some(attempt.result),
+                    last_response: attempt.response,
+                    consecutive_failures: if success {
+                        0
+                    } else if attempt.provider_called {
+                        previous.consecutive_failures + 1
+                    } else {
+                        previous.consecutive_failures
+                    },
+                    next_run_at: attempt.next_run_at,
+                    ipv4_reachability: reachability,
+                    circuit_opened_at: if attempt.circuit_open {
+                        previous.circuit_opened_at.or(Some(now))
+                    } else {
+                        None
+                    },
+                };
+                diesel::replace_into(dyndns_status::table)
+                    .values(status)
+                    .execute(conn)
+            })
+        })
+        .await??;
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+pub struct DynDnsStatusRes {
+    id: i32,
+    hostname: String,
+    provider: ProviderKind,
+    in_sync: bool,
+    paused: Option<DynDnsPause>,
+    #[serde(flatten)]
+    status: DynDnsStatus,
+}
+
+impl DynDnsStatusRes {
+    fn new(record: DynDnsRecord, mut status: DynDnsStatus) -> Self {
+        let paused = record.paused();
+        if paused.is_some() {
+            status.next_run_at = None;
+        }
+        let in_sync = paused.is_none()
+            && status.consecutive_failures == 0
+            && status.last_checked_at.is_some();
+        Self {
+            id: record.id,
+            hostname: record.config.hostname,
+            provider: record.config.provider,
+            in_sync,
+            paused,
+            status,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Selectable, Queryable, AsChangeset, Validate)]
+#[diesel(table_name = settings)]
+#[validate(schema(function = "validate_settings"))]
+pub struct Settings {
+    pub sleep_interval: SleepInterval,
+    #[serde(default = "LookupSources::ipv4")]
+    pub ipv4_lookup: LookupSources,
+    #[serde(default = "LookupSources::ipv6")]
+    pub ipv6_lookup: LookupSources,
+    #[serde(default)]
+    pub lookup_mode: LookupMode,
+    /// Number of sources that must agree in consensus mode.
+    #[serde(default = "Settings::lookup_quorum")]
+    #[validate(range(min = 1))]
+    pub lookup_quorum: i32,
+    #[serde(default)]
+    pub ipv4_detection: Ipv4Detection,
+    /// Refuse to publish IPv4 addresses in private or shared (CGNAT) ranges.
+    #[serde(default)]
+    pub reject_private_ipv4: bool,
+    #[serde(default)]
+    pub ipv6_selection: Ipv6Selection,
+    /// Consecutive failed attempts after which a target's circuit breaker opens.
+    #[serde(default = "Settings::failure_threshold")]
+    #[validate(range(min = 1))]
+    pub failure_threshold: i32,
+}
+
+fn validate_settings(settings: &Settings) -> Result<(), ValidationError> {
+    if let LookupMode::Consensus = settings.lookup_mode {
+        let quorum = settings.lookup_quorum as usize;
+        if quorum > settings.ipv4_lookup.len() || quorum > settings.ipv6_lookup.len() {
+            let mut error = ValidationError::new("lookup_quorum");
+            error.message = Some(Cow::Borrowed(
+                "lookup_quorum exceeds the number of lookup sources",
+            ));
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+/// How the external address is chosen from the configured lookup sources.
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, FromSqlRow, AsExpression)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "kebab-case")]
+pub enum LookupMode {
+    /// Use the first source that answers.
+    #[default]
+    Fallback,
+    /// Query every source at once and require a quorum to agree.
+    Consensus,
+}
+
+impl LookupMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Fallback => "fallback",
+            Self::Consensus => "consensus",
+        }
+    }
+}
+
+impl ToSql<Text, Sqlite> for LookupMode {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for LookupMode {
+    fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        match value.as_str() {
+            "fallback" => Ok(Self::Fallback),
+            "consensus" => Ok(Self::Consensus),
+            _ => Err(format!("Unrecognized lookup mode {}", value).into()),
+        }
+    }
+}
+
+/// Where the IPv4 address is taken from.
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, FromSqlRow, AsExpression)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "kebab-case")]
+pub enum Ipv4Detection {
+    /// Use the public address assigned to the interface.
+    Interface,
+    /// Ask the configured lookup sources.
+    #[default]
+    External,
+    /// Use the interface address, asking the lookup sources when it has none.
+    InterfaceThenExternal,
+}
+
+impl Ipv4Detection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Interface => "interface",
+            Self::External => "external",
+            Self::InterfaceThenExternal => "interface-then-external",
+        }
+    }
+}
+
+impl ToSql<Text, Sqlite> for Ipv4Detection {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for Ipv4Detection {
+    fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        match value.as_str() {
+            "interface" => Ok(Self::Interface),
+            "external" => Ok(Self::External),
+            "interface-then-external" => Ok(Self::InterfaceThenExternal),
+            _ => Err(format!("Unrecognized ipv4 detection {}", value).into()),
+        }
+    }
+}
+
+/// Which interface IPv6 addresses are eligible for publishing.
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, FromSqlRow, AsExpression)]
+#[diesel(sql_type = Text)]
+#[serde(tag = "policy", rename_all = "kebab-case")]
+pub enum Ipv6Selection {
+    /// Every address except link-local ones.
+    All,
+    /// Skip temporary, deprecated, tentative and ULA addresses, preferring EUI-64 ones.
+    #[default]
+    Stable,
+    /// Only usable global addresses whose interface identifier matches `suffix`.
+    Suffix { suffix: Ipv6Addr },
+}
+
+impl ToSql<Text, Sqlite> for Ipv6Selection {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(serde_json::to_string(self)?);
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for Ipv6Selection {
+    fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        Ok(serde_json::from_str(&value)?)
+    }
+}
+
+/// Where the records currently published for a hostname are looked up.
+#[derive(Debug, Clone, Deserialize, Serialize, FromSqlRow, AsExpression)]
+#[diesel(sql_type = Text)]
+#[serde(tag = "mode", rename_all = "kebab-case")]
+pub enum RecordLookup {
+    /// Ask every nameserver in the NS set of the hostname's zone.
+    Authoritative,
+    /// Ask one resolver, e.g. `1.1.1.1` or `ns1.example.net:5353`.
+    Resolver { server: String },
+}
+
+impl ToSql<Text, Sqlite> for RecordLookup {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(serde_json::to_string(self)?);
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for RecordLookup {
+    fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        Ok(serde_json::from_str(&value)?)
+    }
+}
+
+/// Ordered list of HTTP, DNS or STUN sources queried for the external address.
+#[derive(Debug, Clone, Deserialize, Serialize, FromSqlRow, AsExpression)]
+#[diesel(sql_type = Text)]
+#[serde(try_from = "Vec<LookupSource>")]
+pub struct LookupSources(Vec<LookupSource>);
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LookupSource {
+    pub url: String,
+    #[serde(default)]
+    pub parser: LookupParserKind,
+    /// JSON pointer or regular expression used to extract the address from the body.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expression: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LookupParserKind {
+    #[default]
+    PlainText,
+    JsonPointer,
+    Regex,
+}
+
+impl LookupSources {
+    fn ipv4() -> Self {
+        Self::single("https://api-ipv4.ip.sb/ip")
+    }
+
+    fn ipv6() -> Self {
+        Self::single("https://api-ipv6.ip.sb/ip")
+    }
+
+    fn single(url: &str) -> Self {
+        Self(vec![LookupSource {
+            url: url.to_owned(),
+            parser: LookupParserKind::PlainText,
+            expression: None,
+        }])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &LookupSource> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Query described by a `dns://resolver[:port]/name[?type=TXT]` lookup source.
+#[derive(Debug, Clone)]
+pub struct DnsLookupTarget {
+    pub resolver: String,
+    pub name: String,
+    /// Record type to query; the address family's A/AAAA record when unset.
+    pub record_type: Option<DnsRecordType>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum DnsRecordType {
+    A,
+    Aaaa,
+    Txt,
+}
+
+impl DnsRecordType {
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_uppercase().as_str() {
+            "A" => Some(Self::A),
+            "AAAA" => Some(Self::Aaaa),
+            "TXT" => Some(Self::Txt),
+            _ => None,
+        }
+    }
+}
+
+impl LookupSource {
+    /// Returns the `host[:port]` of `stun://` sources.
+    pub fn stun_server(&self) -> Option<&str> {
+        self.url
+            .strip_prefix("stun://")
+            .map(|server| server.trim_end_matches('/'))
+    }
+
+    /// Returns the DNS query for `dns://` sources, `None` for other sources.
+    pub fn dns_target(&self) -> Result<Option<DnsLookupTarget>, String> {
+        let uri = self
+            .url
+            .parse::<Uri>()
+            .map_err(|err| format!("invalid lookup url {}: {}", self.url, err))?;
+        if uri.scheme_str() != Some("dns") {
+            return Ok(None);
+        }
+        let resolver = uri
+            .authority()
+            .map(|authority| authority.as_str().to_owned())
+            .ok_or_else(|| format!("dns lookup url requires a resolver: {}", self.url))?;
+        let name = uri.path().trim_matches('/');
+        if name.is_empty() {
+            return Err(format!("dns lookup url requires a name: {}", self.url));
+        }
+        let mut record_type = None;
+        for param in uri.query().unwrap_or_default().split('&') {
+            if param.is_empty() {
+                continue;
+            }
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            match key {
+                "type" => {
+                    record_type = Some(DnsRecordType::parse(value).ok_or_else(|| {
+                        format!("dns lookup type must be A, AAAA or TXT: {}", value)
+                    })?)
+                }
+                _ => {
+                    return Err(format!(
+                        "unknown dns lookup parameter {}: {}",
+                        key, self.url
+                    ));
+                }
+            }
+        }
+        Ok(Some(DnsLookupTarget {
+            resolver,
+            name: name.to_owned(),
+            record_type,
+        }))
+    }
+
+    fn validate_expression(&self) -> Result<(), String> {
+        let expression = self.expression.as_deref();
+        match (self.parser, expression) {
+            (LookupParserKind::PlainText, None) => Ok(()),
+            (LookupParserKind::PlainText, Some(_)) => Err(format!(
+                "plain-text parser does not take an expression: {}",
+                self.url
+            )),
+            (_, None) => Err(format!(
+                "lookup parser requires an expression: {}",
+                self.url
+            )),
+            (LookupParserKind::JsonPointer, Some(pointer)) => {
+                if pointer.is_empty() || pointer.starts_with('/') {
+                    Ok(())
+                } else {
+                    Err(format!("json pointer must start with '/': {}", pointer))
+                }
+            }
+            (LookupParserKind::Regex, Some(pattern)) => regex::Regex::new(pattern)
+                .map(|_| ())
+                .map_err(|err| format!("invalid lookup regex {}: {}", pattern, err)),
+        }
+    }
+}
+
+impl TryFrom<Vec<LookupSource>> for LookupSources {
+    type Error = String;
+
+    fn try_from(sources: Vec<LookupSource>) -> Result<Self, Self::Error> {
+        if sources.is_empty() {
+            return Err("at least one lookup source is required".into());
+        }
+        for source in &sources {
+            let uri = source
+                .url
+                .parse::<Uri>()
+                .map_err(|err| format!("invalid lookup url {}: {}", source.url, err))?;
+            if !matches!(uri.scheme_str(), Some("http" | "https" | "dns" | "stun"))
+                || uri.host().is_none()
+            {
+                return Err(format!(
+                    "lookup url must be http(s), dns or stun: {}",
+                    source.url
+                ));
+            }
+            source.dns_target()?;
+            if source.stun_server().is_some() {
+                if !uri.path().trim_matches('/').is_empty() || uri.query().is_some() {
+                    return Err(format!(
+                        "stun lookup url takes only a server: {}",
+                        source.url
+                    ));
+                }
+                if source.parser != LookupParserKind::PlainText {
+                    return Err(format!(
+                        "stun lookup does not take a parser: {}",
+                        source.url
+                    ));
+                }
+            }
+            source.validate_expression()?;
+        }
+        Ok(Self(sources))
+    }
+}
+
+impl ToSql<Text, Sqlite> for LookupSources {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(serde_json::to_string(self)?);
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for LookupSources {
+    fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        Ok(serde_json::from_str(&value)?)
+    }
+}
+
+impl Settings {
+    fn lookup_quorum() -> i32 {
+        2
+    }
+
+    fn failure_threshold() -> i32 {
+        5
+    }
+
+    pub async fn get(conn: &DbConn) -> Result<Settings, Error> {
+        conn.interact(|conn| {
+            settings::table
+                .find(1)
+                .select(Settings::as_select())
+                .first(conn)
+        })
+        .await?
+        .map_err(|e| e.into())
+    }
+
+    pub async fn update(conn: &DbConn, settings: Settings) -> Result<Settings, Error> {
+        conn.interact(|conn| {
+            diesel::update(settings::table.find(1))
+                .set(settings)
+                .returning(Settings::as_returning())
+                .get_result(conn)
+        })
+        .await?
+        .map_err(|e| e.into())
     }
 }
 
 #[derive(Serialize, Selectable, Queryable, Insertable)]
 #[diesel(table_name=history)]
 pub struct History {
+    dyndns_id: Option<i32>,
     old_ip: Option<String>,
     new_ip: String,
     version: HistoryIpVersion,
     updated: NaiveDateTime,
+    event: HistoryEvent,
+    old_prefix: Option<String>,
+    new_prefix: Option<String>,
+    /// Whether the new address was seen in DNS after the update, unset when not checked.
+    verified: Option<bool>,
 }
 
 pub type BoxHistoryOrder =
@@ -318,28 +1421,45 @@ impl History {
 
     pub async fn insert_v4(
         conn: &DbConn,
+        dyndns_id: i32,
         old_ip: &Option<Ipv4Addr>,
         new_ip: &Ipv4Addr,
-    ) -> Result<(), Error> {
+        event: HistoryEvent,
+    ) -> Result<i32, Error> {
         let old_ip = old_ip.as_ref().map(|v| v.to_string());
         let new_ip = new_ip.to_string();
         let version = HistoryIpVersion::V4;
         let h = History {
+            dyndns_id: Some(dyndns_id),
             old_ip,
             new_ip,
             version,
             updated: Utc::now().naive_utc(),
+            event,
+            old_prefix: None,
+            new_prefix: None,
+            verified: None,
         };
-        conn.interact(|conn| diesel::insert_into(history::table).values(h).execute(conn))
+        let id = conn
+            .interact(|conn| {
+                diesel::insert_into(history::table)
+                    .values(h)
+                    .returning(history::id)
+                    .get_result(conn)
+            })
             .await??;
-        Ok(())
+        Ok(id)
     }
 
+    /// `prefix` records the old and new delegated prefix for suffix targets.
     pub async fn insert_v6(
         conn: &DbConn,
+        dyndns_id: i32,
         old_ip: &Option<Vec<Ipv6Addr>>,
-        new_ip: &Vec<Ipv6Addr>,
-    ) -> Result<(), Error> {
+        new_ip: &[Ipv6Addr],
+        event: HistoryEvent,
+        prefix: Option<(Option<String>, String)>,
+    ) -> Result<i32, Error> {
         let old_ip = old_ip.as_ref().map(|v| {
             v.iter()
                 .map(|&x| x.to_string())
@@ -353,19 +1473,46 @@ impl History {
             .join(",");
         let version = HistoryIpVersion::V6;
         let h = History {
+            dyndns_id: Some(dyndns_id),
             old_ip,
             new_ip,
             version,
             updated: Utc::now().naive_utc(),
+            event,
+            old_prefix: prefix.as_ref().and_then(|(old, _)| old.clone()),
+            new_prefix: prefix.map(|(_, new)| new),
+            verified: None,
         };
-        conn.interact(|conn| diesel::insert_into(history::table).values(h).execute(conn))
+        let id = conn
+            .interact(|conn| {
+                diesel::insert_into(history::table)
+                    .values(h)
+                    .returning(history::id)
+                    .get_result(conn)
+            })
             .await??;
+        Ok(id)
+    }
+
+    /// Stores the outcome of the post-update DNS check on the given entries.
+    pub async fn set_verified(conn: &DbConn, ids: Vec<i32>, verified: bool) -> Result<(), Error> {
+        conn.interact(move |conn| {
+            diesel::update(history::table.filter(history::id.eq_any(ids)))
+                .set(history::verified.eq(Some(verified)))
+                .execute(conn)
+        })
+        .await??;
         Ok(())
     }
 
-    async fn get_new_ip(conn: &DbConn, version: HistoryIpVersion) -> Result<Option<String>, Error> {
-        conn.interact(|conn| {
+    async fn get_new_ip(
+        conn: &DbConn,
+        dyndns_id: i32,
+        version: HistoryIpVersion,
+    ) -> Result<Option<String>, Error> {
+        conn.interact(move |conn| {
             history::table
+                .filter(history::dyndns_id.eq(dyndns_id))
                 .filter(history::version.eq(version))
                 .select(history::new_ip)
                 .order(history::id.desc())
@@ -376,23 +1523,48 @@ impl History {
         .map_err(|e| e.into())
     }
 
-    pub async fn get_v4(conn: &DbConn) -> Result<Option<String>, Error> {
-        Self::get_new_ip(conn, HistoryIpVersion::V4).await
+    pub async fn last_updated(
+        conn: &DbConn,
+        dyndns_id: i32,
+    ) -> Result<Option<NaiveDateTime>, Error> {
+        conn.interact(move |conn| {
+            history::table
+                .filter(history::dyndns_id.eq(dyndns_id))
+                .select(diesel::dsl::max(history::updated))
+                .first(conn)
+        })
+        .await?
+        .map_err(|e| e.into())
     }
 
-    pub async fn get_v6(conn: &DbConn) -> Result<Option<(Option<String>, String)>, Error> {
-        Ok(Self::get_current(conn, HistoryIpVersion::V6)
-            .await?
-            .map(|history| (history.old_ip, history.new_ip)))
+    pub async fn get_v4(conn: &DbConn, dyndns_id: i32) -> Result<Option<String>, Error> {
+        Self::get_new_ip(conn, dyndns_id, HistoryIpVersion::V4).await
+    }
+
+    pub async fn get_v6(
+        conn: &DbConn,
+        dyndns_id: i32,
+    ) -> Result<Option<(Option<String>, String)>, Error> {
+        Ok(
+            Self::get_current(conn, HistoryIpVersion::V6, Some(dyndns_id))
+                .await?
+                .map(|history| (history.old_ip, history.new_ip)),
+        )
     }
 
     pub async fn get_current(
         conn: &DbConn,
         version: HistoryIpVersion,
+        dyndns_id: Option<i32>,
     ) -> Result<Option<History>, Error> {
-        conn.interact(|conn| {
-            history::table
+        conn.interact(move |conn| {
+            let mut query = history::table
                 .filter(history::version.eq(version))
+                .into_boxed();
+            if let Some(dyndns_id) = dyndns_id {
+                query = query.filter(history::dyndns_id.eq(dyndns_id));
+            }
+            query
                 .select(Self::as_select())
                 .order(history::id.desc())
                 .first(conn)
@@ -435,6 +1607,45 @@ impl ToSql<Integer, diesel::sqlite::Sqlite> for HistoryIpVersion {
     }
 }
 
+/// Why a history row was written.
+#[repr(i32)]
+#[derive(Debug, FromSqlRow, AsExpression, Clone, Copy, Deserialize, Serialize)]
+#[diesel(sql_type = Integer)]
+pub enum HistoryEvent {
+    /// The detected address differed from the last recorded one.
+    Change,
+    /// An update was forced through the API.
+    Forced,
+    /// The unchanged address was pushed again because the record reached its max age.
+    Refresh,
+}
+
+impl FromSql<Integer, diesel::sqlite::Sqlite> for HistoryEvent {
+    fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> diesel::deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            1 => Ok(Self::Change),
+            2 => Ok(Self::Forced),
+            3 => Ok(Self::Refresh),
+            x => Err(format!("Unrecognized variant {}", x).into()),
+        }
+    }
+}
+
+impl ToSql<Integer, diesel::sqlite::Sqlite> for HistoryEvent {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, diesel::sqlite::Sqlite>,
+    ) -> diesel::serialize::Result {
+        let v = match self {
+            Self::Change => 1,
+            Self::Forced => 2,
+            Self::Refresh => 3,
+        };
+        out.set_value(v);
+        Ok(IsNull::No)
+    }
+}
+
 #[derive(Serialize)]
 pub struct HistoryRes {
     total: i64,
@@ -447,6 +1658,93 @@ impl HistoryRes {
     }
 }
 
+#[derive(Debug, Clone, Copy, Serialize, FromSqlRow, AsExpression)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub enum EventLevel {
+    Warning,
+}
+
+impl EventLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Warning => "warning",
+        }
+    }
+}
+
+impl ToSql<Text, Sqlite> for EventLevel {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for EventLevel {
+    fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        match value.as_str() {
+            "warning" => Ok(Self::Warning),
+            _ => Err(format!("Unrecognized event level {}", value).into()),
+        }
+    }
+}
+
+/// Noteworthy scheduler occurrences that are not tied to an update.
+#[derive(Serialize, Selectable, Queryable)]
+#[diesel(table_name = events)]
+pub struct Event {
+    id: i32,
+    level: EventLevel,
+    message: String,
+    created_at: NaiveDateTime,
+}
+
+impl Event {
+    pub async fn insert(conn: &DbConn, level: EventLevel, message: String) -> Result<(), Error> {
+        conn.interact(move |conn| {
+            diesel::insert_into(events::table)
+                .values((
+                    events::level.eq(level),
+                    events::message.eq(message),
+                    events::created_at.eq(Utc::now().naive_utc()),
+                ))
+                .execute(conn)
+        })
+        .await??;
+        Ok(())
+    }
+
+    pub async fn paginate(
+        conn: &DbConn,
+        page: usize,
+        per_page: i64,
+    ) -> Result<(Vec<Self>, i64), Error> {
+        conn.interact(move |conn| {
+            events::table
+                .order(events::id.desc())
+                .select(Self::as_select())
+                .paginate(page as i64)
+                .per_page(per_page)
+                .load_and_total(conn)
+        })
+        .await?
+        .map_err(|e| e.into())
+    }
+}
+
+#[derive(Serialize)]
+pub struct EventRes {
+    total: i64,
+    events: Vec<Event>,
+}
+
+impl EventRes {
+    pub fn new(total: i64, events: Vec<Event>) -> Self {
+        Self { total, events }
+    }
+}
+
 #[derive(Debug, Queryable, Selectable, Insertable)]
 #[diesel(table_name = refresh_tokens)]
 pub struct RefreshTokenRecord {
@@ -5,6 +5,7 @@ use diesel::{
     sql_types::BigInt,
     sqlite::Sqlite,
 };
+use serde::Deserialize;
 
 pub trait Paginate: Sized {
     fn paginate(self, page: i64) -> Paginated<Self>;
@@ -23,6 +24,23 @@ impl<T> Paginate for T {
 
 const DEFAULT_PER_PAGE: i64 = 10;
 
+/// `page` and `per_page` query parameters of the list endpoints.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct Pagination {
+    pub page: usize,
+    pub per_page: i64,
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Self {
+            page: 1,
+            per_page: DEFAULT_PER_PAGE,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, QueryId)]
 pub struct Paginated<T> {
     query: T,
@@ -45,7 +63,7 @@ impl<T> Paginated<T> {
         Self: LoadQuery<'a, SqliteConnection, (U, i64)>,
     {
         let results = self.load::<(U, i64)>(conn)?;
-        let total = results.get(0).map(|x| x.1).unwrap_or(0);
+        let total = results.first().map(|x| x.1).unwrap_or(0);
         let records = results.into_iter().map(|x| x.0).collect();
         Ok((records, total))
     }
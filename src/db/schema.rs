@@ -9,17 +9,45 @@ diesel::table! {
         hostname -> Text,
         ip -> Integer,
         interface -> Text,
-        sleep_interval -> BigInt,
+        provider -> Text,
+        options -> Text,
+        paused_reason -> Nullable<Text>,
+        paused_at -> Nullable<Timestamp>,
+        max_age -> Nullable<BigInt>,
+        ipv6_suffix -> Nullable<Text>,
+        ipv6_prefix_length -> Nullable<Integer>,
+        verify -> Nullable<Text>,
+        compare_dns -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    dyndns_status (dyndns_id) {
+        dyndns_id -> Integer,
+        last_checked_at -> Nullable<Timestamp>,
+        last_attempt_at -> Nullable<Timestamp>,
+        last_success_at -> Nullable<Timestamp>,
+        last_result -> Nullable<Text>,
+        last_response -> Nullable<Text>,
+        consecutive_failures -> Integer,
+        next_run_at -> Nullable<Timestamp>,
+        ipv4_reachability -> Nullable<Text>,
+        circuit_opened_at -> Nullable<Timestamp>,
     }
 }
 
 diesel::table! {
     history (id) {
         id -> Integer,
+        dyndns_id -> Nullable<Integer>,
         old_ip -> Nullable<Text>,
         new_ip -> Text,
         version -> Integer,
         updated -> Timestamp,
+        event -> Integer,
+        old_prefix -> Nullable<Text>,
+        new_prefix -> Nullable<Text>,
+        verified -> Nullable<Bool>,
     }
 }
 
@@ -40,4 +68,36 @@ diesel::table! {
     }
 }
 
-diesel::allow_tables_to_appear_in_same_query!(dyndns, history, refresh_tokens, auth_secrets,);
+diesel::table! {
+    settings (id) {
+        id -> Integer,
+        sleep_interval -> BigInt,
+        ipv4_lookup -> Text,
+        ipv6_lookup -> Text,
+        lookup_mode -> Text,
+        lookup_quorum -> Integer,
+        ipv4_detection -> Text,
+        reject_private_ipv4 -> Bool,
+        ipv6_selection -> Text,
+        failure_threshold -> Integer,
+    }
+}
+
+diesel::table! {
+    events (id) {
+        id -> Integer,
+        level -> Text,
+        message -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::allow_tables_to_appear_in_same_query!(
+    dyndns,
+    dyndns_status,
+    history,
+    refresh_tokens,
+    auth_secrets,
+    settings,
+    events,
+);
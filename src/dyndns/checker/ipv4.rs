@@ -1,55 +1,145 @@
-use std::net::Ipv4Addr;
-
-use crate::Error;
+use std::{
+    net::Ipv4Addr,
+    sync::{Mutex, OnceLock},
+};
 
-use super::super::{
-    http_client::HttpClient,
-    lookup::{HttpIpLookup, IpLookup, IpsbLookup, PlainTextIpParser},
+use crate::{
+    Error,
+    db::{Ipv4Detection, Ipv4Reachability},
 };
+
+use super::super::lookup::{ExternalIpLookup, IpLookup, LocalIpv4Lookup, LookupAnswer, is_shared};
 use super::{CheckResult, IpChecker};
 
 pub type Ipv4CheckResult = CheckResult<Option<Ipv4Addr>, Option<Ipv4Addr>, Option<Ipv4Addr>>;
 
 pub struct Ipv4Checker<'a> {
-    lookup: IpsbLookup<'a, Ipv4Addr>,
-    previous_ip: Option<Ipv4Addr>,
+    interface: &'a str,
+    detection: Ipv4Detection,
+    reject_private: bool,
+    local_lookup: LocalIpv4Lookup<'a>,
+    lookup: ExternalIpLookup<'a, Ipv4Addr>,
+    reachability: OnceLock<Ipv4Reachability>,
+    warnings: Mutex<Vec<String>>,
 }
 
 impl<'a> Ipv4Checker<'a> {
-    pub fn new(client: &'a HttpClient, interface: &'a str, previous_ip: Option<Ipv4Addr>) -> Self {
+    pub fn new(
+        interface: &'a str,
+        detection: Ipv4Detection,
+        reject_private: bool,
+        lookup: ExternalIpLookup<'a, Ipv4Addr>,
+    ) -> Self {
         Self {
-            lookup: HttpIpLookup::<PlainTextIpParser, Ipv4Addr>::ipsb(client, interface),
-            previous_ip,
+            interface,
+            detection,
+            reject_private,
+            local_lookup: LocalIpv4Lookup::new(interface),
+            lookup,
+            reachability: OnceLock::new(),
+            warnings: Mutex::new(vec![]),
         }
     }
-}
 
-impl<'a> IpChecker for Ipv4Checker<'a> {
-    type Previous = Option<Ipv4Addr>;
-    type Current = Option<Ipv4Addr>;
-    type External = Option<Ipv4Addr>;
+    /// Classification of the last detected address, if the interface has IPv4.
+    pub fn reachability(&self) -> Option<Ipv4Reachability> {
+        self.reachability.get().copied()
+    }
 
-    async fn check(self) -> Result<Ipv4CheckResult, Error> {
-        debug!("check v4");
+    fn classify(&self, address: Ipv4Addr) -> Option<Ipv4Reachability> {
+        let interface_addresses = match self.local_lookup.addresses() {
+            Ok(addresses) if !addresses.is_empty() => addresses,
+            Ok(_) => return None,
+            Err(err) => {
+                debug!(
+                    "failed to list ipv4 addresses on {}: {}",
+                    self.interface, err
+                );
+                return None;
+            }
+        };
+        Some(
+            if is_shared(&address) || interface_addresses.iter().any(is_shared) {
+                Ipv4Reachability::Cgnat
+            } else if !address.is_private() && interface_addresses.contains(&address) {
+                Ipv4Reachability::Direct
+            } else {
+                Ipv4Reachability::Nat
+            },
+        )
+    }
 
-        let Self {
-            lookup,
-            previous_ip,
-        } = self;
+    async fn resolve(&self) -> Result<LookupAnswer<Ipv4Addr>, Error> {
+        match self.detection {
+            Ipv4Detection::Interface => self.local().await,
+            Ipv4Detection::External => self.lookup.lookup().await,
+            Ipv4Detection::InterfaceThenExternal => match self.local().await {
+                Ok(answer) => Ok(answer),
+                Err(err) => {
+                    debug!("no public ipv4 on {}: {}", self.interface, err);
+                    self.lookup.lookup().await
+                }
+            },
+        }
+    }
 
+    async fn local(&self) -> Result<LookupAnswer<Ipv4Addr>, Error> {
+        let address = self.local_lookup.lookup().await?;
+        Ok(LookupAnswer {
+            address,
+            source: self.interface.to_owned(),
+        })
+    }
+
+    pub fn take_warnings(&self) -> Vec<String> {
+        let mut warnings = std::mem::take(&mut *self.warnings.lock().unwrap());
+        warnings.extend(self.lookup.take_warnings());
+        warnings
+    }
+
+    pub fn compare(
+        &self,
+        previous_ip: Option<Ipv4Addr>,
+        answer: &LookupAnswer<Ipv4Addr>,
+        force: bool,
+    ) -> Ipv4CheckResult {
+        let current_ip = answer.address;
         debug!("{:?}", previous_ip);
-        let current_ip = lookup.lookup().await?;
 
-        if let Some(existing) = previous_ip {
-            if existing == current_ip {
-                return Ok(Ipv4CheckResult::default());
-            }
+        if !force
+            && let Some(existing) = previous_ip
+            && existing == current_ip
+        {
+            return Ipv4CheckResult::default();
         }
 
         let previous = previous_ip;
         let current = Some(current_ip);
         let external = current;
 
-        Ok(Ipv4CheckResult::new(previous, current, external))
+        let mut result =
+            Ipv4CheckResult::new(previous, current, external).with_source(answer.source.as_str());
+        result.reachability = self.reachability();
+        result
+    }
+}
+
+impl<'a> IpChecker for Ipv4Checker<'a> {
+    type Detected = LookupAnswer<Ipv4Addr>;
+
+    async fn detect(&self) -> Result<Self::Detected, Error> {
+        debug!("check v4");
+        let answer = self.resolve().await?;
+        if let Some(reachability) = self.classify(answer.address) {
+            let _ = self.reachability.set(reachability);
+        }
+
+        let address = answer.address;
+        if self.reject_private && (address.is_private() || is_shared(&address)) {
+            let err = Error::ipv4_not_public(format!("{} (via {})", address, answer.source));
+            self.warnings.lock().unwrap().push(err.to_string());
+            return Err(err);
+        }
+        Ok(answer)
     }
 }
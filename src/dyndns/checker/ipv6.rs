@@ -1,54 +1,93 @@
 use std::{collections::HashSet, net::Ipv6Addr};
 
-use crate::Error;
+use tokio::sync::OnceCell;
 
-use super::super::{
-    http_client::HttpClient,
-    lookup::{HttpIpLookup, IpLookup, IpsbLookup, LocalIpv6Lookup, PlainTextIpParser},
+use crate::{
+    Error,
+    db::{DynDNS, Ipv6Selection},
 };
+
+use super::super::lookup::{ExternalIpLookup, IpLookup, LocalIpv6Lookup, LookupAnswer};
 use super::{CheckResult, IpChecker};
 
 pub type Ipv6CheckResult =
-    CheckResult<Option<Vec<Ipv6Addr>>, Option<Vec<Ipv6Addr>>, Option<Ipv6Addr>>;
+    CheckResult<Option<Vec<Ipv6Addr>>, Option<Vec<Ipv6Addr>>, Option<Vec<Ipv6Addr>>>;
 
 pub struct Ipv6Checker<'a> {
+    interface: &'a str,
     local_lookup: LocalIpv6Lookup<'a>,
-    external_lookup: IpsbLookup<'a, Ipv6Addr>,
-    history: Option<Ipv6HistorySnapshot>,
+    external_lookup: ExternalIpLookup<'a, Ipv6Addr>,
+    external: OnceCell<Option<LookupAnswer<Ipv6Addr>>>,
 }
 
 impl<'a> Ipv6Checker<'a> {
     pub fn new(
-        client: &'a HttpClient,
         interface: &'a str,
-        history: Option<Ipv6HistorySnapshot>,
+        selection: Ipv6Selection,
+        external_lookup: ExternalIpLookup<'a, Ipv6Addr>,
     ) -> Self {
         Self {
-            local_lookup: LocalIpv6Lookup::new(interface),
-            external_lookup: HttpIpLookup::<PlainTextIpParser, Ipv6Addr>::ipsb(client, interface),
-            history,
+            interface,
+            local_lookup: LocalIpv6Lookup::new(interface, selection),
+            external_lookup,
+            external: OnceCell::new(),
         }
     }
-}
 
-impl<'a> IpChecker for Ipv6Checker<'a> {
-    type Previous = Option<Vec<Ipv6Addr>>;
-    type Current = Option<Vec<Ipv6Addr>>;
-    type External = Option<Ipv6Addr>;
+    pub fn take_warnings(&self) -> Vec<String> {
+        self.external_lookup.take_warnings()
+    }
 
-    async fn check(self) -> Result<Ipv6CheckResult, Error> {
-        debug!("check v6");
+    /// Compares the addresses built from the interface prefixes and a fixed
+    /// suffix, so only a prefix change is reported.
+    pub fn compare_prefix(
+        &self,
+        history: Option<Ipv6HistorySnapshot>,
+        interface_addresses: &[Ipv6Addr],
+        suffix: &Ipv6Suffix,
+        force: bool,
+        publish_all: bool,
+    ) -> Ipv6CheckResult {
+        let current = suffix.derive(interface_addresses);
+        let previous = history.map(|history| history.latest);
 
-        let Self {
-            local_lookup,
-            external_lookup,
-            history,
-        } = self;
+        if !force
+            && let Some(previous) = previous.as_ref()
+            && previous.len() == current.len()
+            && current.iter().all(|address| previous.contains(address))
+        {
+            return Ipv6CheckResult::default();
+        }
 
-        let interface_addresses = local_lookup.lookup().await?;
+        let source = match current.first() {
+            Some(address) => format!("{} on {}", suffix.prefix(address), self.interface),
+            None => self.interface.to_owned(),
+        };
+        let external = if publish_all {
+            current.clone()
+        } else {
+            current.iter().take(1).copied().collect()
+        };
+        let external = (!external.is_empty()).then_some(external);
+        Ipv6CheckResult::new(previous, Some(current), external).with_source(source)
+    }
 
-        let (previous_addresses, current_addresses) =
-            if let Some(Ipv6HistorySnapshot { previous, latest }) = history {
+    pub async fn compare(
+        &self,
+        history: Option<Ipv6HistorySnapshot>,
+        interface_addresses: &[Ipv6Addr],
+        force: bool,
+        publish_all: bool,
+    ) -> Ipv6CheckResult {
+        if publish_all {
+            return self.compare_set(history, interface_addresses, force);
+        }
+
+        let (previous_addresses, current_addresses) = match history {
+            Some(Ipv6HistorySnapshot { latest, .. }) if force => {
+                (Some(latest), Some(interface_addresses.to_vec()))
+            }
+            Some(Ipv6HistorySnapshot { previous, latest }) => {
                 let estimated = latest.len() + previous.as_ref().map_or(0, |p| p.len());
                 let mut known_addresses: HashSet<&Ipv6Addr> = HashSet::with_capacity(estimated);
 
@@ -63,7 +102,7 @@ impl<'a> IpChecker for Ipv6Checker<'a> {
                 }
 
                 let (current, retained): (Vec<Ipv6Addr>, Vec<Ipv6Addr>) = interface_addresses
-                    .into_iter()
+                    .iter()
                     .partition(|addr| !known_addresses.contains(addr));
 
                 let current = (!current.is_empty()).then_some(current);
@@ -74,25 +113,161 @@ impl<'a> IpChecker for Ipv6Checker<'a> {
                 };
 
                 (previous, current)
-            } else {
-                (None, Some(interface_addresses))
-            };
+            }
+            None => (None, Some(interface_addresses.to_vec())),
+        };
 
-        let external = match current_addresses.as_ref() {
-            Some(current) if current.len() == 1 => Some(current[0]),
-            Some(_) => Some(external_lookup.lookup().await?),
+        let answer = match current_addresses.as_ref() {
+            Some(current) if current.len() == 1 => Some(LookupAnswer {
+                address: current[0],
+                source: self.interface.to_owned(),
+            }),
+            Some(_) => self.external().await,
             None => None,
         };
 
+        let external = answer.as_ref().map(|answer| vec![answer.address]);
         if external.is_some() {
             debug!("external ipv6 address: {:?}", &external);
         }
 
-        Ok(Ipv6CheckResult::new(
-            previous_addresses,
-            current_addresses,
-            external,
-        ))
+        let result = Ipv6CheckResult::new(previous_addresses, current_addresses, external);
+        match answer {
+            Some(answer) => result.with_source(answer.source),
+            None => result,
+        }
+    }
+
+    /// Compares against the AAAA records currently published instead of the
+    /// history. A single published address counts as current while it is still
+    /// a candidate; with `publish_all` the whole set has to match.
+    pub async fn compare_published(
+        &self,
+        published: Vec<Ipv6Addr>,
+        interface_addresses: &[Ipv6Addr],
+        suffix: Option<&Ipv6Suffix>,
+        force: bool,
+        publish_all: bool,
+    ) -> Ipv6CheckResult {
+        let candidates = match suffix {
+            Some(suffix) => suffix.derive(interface_addresses),
+            None => interface_addresses.to_vec(),
+        };
+        let unchanged = if publish_all {
+            published.len() == candidates.len()
+                && candidates.iter().all(|address| published.contains(address))
+        } else {
+            !published.is_empty() && published.iter().all(|address| candidates.contains(address))
+        };
+        if unchanged && !force {
+            return Ipv6CheckResult::default();
+        }
+
+        let history = (!published.is_empty()).then(|| Ipv6HistorySnapshot::new(None, published));
+        match suffix {
+            Some(suffix) => {
+                self.compare_prefix(history, interface_addresses, suffix, true, publish_all)
+            }
+            None => {
+                self.compare(history, interface_addresses, true, publish_all)
+                    .await
+            }
+        }
+    }
+
+    /// Publishes the whole address set whenever it differs from the last one,
+    /// so removed addresses are withdrawn as well.
+    fn compare_set(
+        &self,
+        history: Option<Ipv6HistorySnapshot>,
+        interface_addresses: &[Ipv6Addr],
+        force: bool,
+    ) -> Ipv6CheckResult {
+        let previous = history.map(|history| history.latest);
+        let unchanged = previous.as_ref().is_some_and(|latest| {
+            latest.len() == interface_addresses.len()
+                && interface_addresses.iter().all(|addr| latest.contains(addr))
+        });
+        if unchanged && !force {
+            return Ipv6CheckResult::new(previous, None, None);
+        }
+
+        let current = interface_addresses.to_vec();
+        let external = (!current.is_empty()).then(|| current.clone());
+        Ipv6CheckResult::new(previous, Some(current), external).with_source(self.interface)
+    }
+
+    async fn external(&self) -> Option<LookupAnswer<Ipv6Addr>> {
+        self.external
+            .get_or_init(|| async {
+                match self.external_lookup.lookup().await {
+                    Ok(addr) => Some(addr),
+                    Err(err) => {
+                        error!("{}", err);
+                        None
+                    }
+                }
+            })
+            .await
+            .clone()
+    }
+}
+
+impl<'a> IpChecker for Ipv6Checker<'a> {
+    type Detected = Vec<Ipv6Addr>;
+
+    async fn detect(&self) -> Result<Vec<Ipv6Addr>, Error> {
+        debug!("check v6");
+        self.local_lookup.lookup().await
+    }
+}
+
+/// Fixed interface identifier combined with the interface's current prefix.
+pub struct Ipv6Suffix {
+    suffix: Ipv6Addr,
+    prefix_length: u32,
+}
+
+impl Ipv6Suffix {
+    const DEFAULT_PREFIX_LENGTH: i32 = 64;
+
+    pub fn from_config(config: &DynDNS) -> Option<Self> {
+        let suffix = config.ipv6_suffix.as_deref()?.parse().ok()?;
+        let prefix_length = config
+            .ipv6_prefix_length
+            .unwrap_or(Self::DEFAULT_PREFIX_LENGTH)
+            .clamp(1, 127) as u32;
+        Some(Self {
+            suffix,
+            prefix_length,
+        })
+    }
+
+    fn mask(&self) -> u128 {
+        u128::MAX << (128 - self.prefix_length)
+    }
+
+    /// Combines the suffix with every interface prefix, without duplicates.
+    pub fn derive(&self, interface_addresses: &[Ipv6Addr]) -> Vec<Ipv6Addr> {
+        let mut derived = vec![];
+        for address in interface_addresses {
+            let combined = self.combine(address);
+            if !derived.contains(&combined) {
+                derived.push(combined);
+            }
+        }
+        derived
+    }
+
+    pub fn combine(&self, address: &Ipv6Addr) -> Ipv6Addr {
+        let mask = self.mask();
+        Ipv6Addr::from((u128::from(*address) & mask) | (u128::from(self.suffix) & !mask))
+    }
+
+    /// Formats the prefix of `address`, e.g. `2001:db8:1::/64`.
+    pub fn prefix(&self, address: &Ipv6Addr) -> String {
+        let prefix = Ipv6Addr::from(u128::from(*address) & self.mask());
+        format!("{}/{}", prefix, self.prefix_length)
     }
 }
 
@@ -114,3 +289,103 @@ pub fn parse_ipv6_list(input: &str) -> Vec<Ipv6Addr> {
         .filter_map(|value| value.trim().parse().ok())
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    fn suffix(suffix: &str, prefix_length: Value) -> Option<Ipv6Suffix> {
+        let config: DynDNS = serde_json::from_value(json!({
+            "server": "members.example.com",
+            "username": "user",
+            "password": "password",
+            "hostname": "host.example.com",
+            "ip": 2,
+            "interface": "lo",
+            "ipv6_suffix": suffix,
+            "ipv6_prefix_length": prefix_length,
+        }))
+        .unwrap();
+        Ipv6Suffix::from_config(&config)
+    }
+
+    fn addr(address: &str) -> Ipv6Addr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn combines_with_64_prefix_by_default() {
+        let suffix = suffix("::1234:5678", Value::Null).unwrap();
+        let address = addr("2001:db8:1:2:aaaa:bbbb:cccc:dddd");
+        assert_eq!(suffix.combine(&address), addr("2001:db8:1:2::1234:5678"));
+        assert_eq!(suffix.prefix(&address), "2001:db8:1:2::/64");
+    }
+
+    #[test]
+    fn combines_with_56_prefix() {
+        let suffix = suffix("::34:0:0:1234:5678", json!(56)).unwrap();
+        let address = addr("2001:db8:ab:cd12:aaaa:bbbb:cccc:dddd");
+        // The low byte of the fourth group comes from the suffix.
+        assert_eq!(
+            suffix.combine(&address),
+            addr("2001:db8:ab:cd34::1234:5678")
+        );
+        assert_eq!(suffix.prefix(&address), "2001:db8:ab:cd00::/56");
+    }
+
+    #[test]
+    fn handles_edge_prefix_lengths() {
+        let one = suffix("::1", json!(1)).unwrap();
+        assert_eq!(one.combine(&addr("ffff::")), addr("8000::1"));
+        assert_eq!(one.prefix(&addr("ffff::")), "8000::/1");
+
+        let last = suffix("::1", json!(127)).unwrap();
+        assert_eq!(
+            last.combine(&addr("2001:db8::fffe")),
+            addr("2001:db8::ffff")
+        );
+        assert_eq!(last.prefix(&addr("2001:db8::ffff")), "2001:db8::fffe/127");
+    }
+
+    #[test]
+    fn clamps_prefix_length() {
+        let zero = suffix("::1", json!(0)).unwrap();
+        assert_eq!(zero.prefix(&addr("ffff::")), "8000::/1");
+        let full = suffix("::1", json!(128)).unwrap();
+        assert_eq!(full.prefix(&addr("2001:db8::ffff")), "2001:db8::fffe/127");
+    }
+
+    #[test]
+    fn ignores_missing_or_invalid_suffix() {
+        assert!(suffix("not an address", json!(64)).is_none());
+        let config: DynDNS = serde_json::from_value(json!({
+            "server": "members.example.com",
+            "username": "user",
+            "password": "password",
+            "hostname": "host.example.com",
+            "ip": 2,
+            "interface": "lo",
+        }))
+        .unwrap();
+        assert!(Ipv6Suffix::from_config(&config).is_none());
+    }
+
+    #[test]
+    fn derives_one_address_per_prefix() {
+        let suffix = suffix("::1234:5678", json!(64)).unwrap();
+        let derived = suffix.derive(&[
+            addr("2001:db8:1:2::1"),
+            addr("2001:db8:1:2::2"),
+            addr("2001:db8:1:3::1"),
+        ]);
+        assert_eq!(
+            derived,
+            [
+                addr("2001:db8:1:2::1234:5678"),
+                addr("2001:db8:1:3::1234:5678")
+            ]
+        );
+    }
+}
@@ -1,4 +1,6 @@
-use crate::Error;
+use std::{fmt::Display, net::Ipv4Addr};
+
+use crate::{Error, db::Ipv4Reachability};
 
 pub mod ipv4;
 pub mod ipv6;
@@ -8,6 +10,10 @@ pub struct CheckResult<P, C, E> {
     pub previous: P,
     pub current: C,
     pub external: E,
+    /// Where the external address came from.
+    pub source: Option<String>,
+    /// Whether the IPv4 address is reachable inbound; unset for IPv6.
+    pub reachability: Option<Ipv4Reachability>,
 }
 
 impl<P, C, E> Default for CheckResult<P, C, E>
@@ -21,6 +27,8 @@ where
             previous: P::default(),
             current: C::default(),
             external: E::default(),
+            source: None,
+            reachability: None,
         }
     }
 }
@@ -31,29 +39,63 @@ impl<P, C, E> CheckResult<P, C, E> {
             previous,
             current,
             external,
+            source: None,
+            reachability: None,
         }
     }
+
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+}
+
+impl<P, C, T: Published> CheckResult<P, C, Option<T>> {
+    /// Formats the external address along with the source that reported it.
+    pub fn summary(&self) -> Option<String> {
+        let external = self.external.as_ref()?.published();
+        Some(match &self.source {
+            Some(source) => format!("{external} (via {source})"),
+            None => external,
+        })
+    }
+}
+
+/// Addresses about to be published, formatted for logs.
+pub trait Published {
+    fn published(&self) -> String;
+}
+
+impl Published for Ipv4Addr {
+    fn published(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl<T: Display> Published for Vec<T> {
+    fn published(&self) -> String {
+        self.iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",")
+    }
 }
 
-pub trait IpChecker: Send + Sync + Sized {
-    type Previous: Default + Send + Sync;
-    type Current: Default + Send + Sync;
-    type External: Default + Send + Sync;
+pub trait IpChecker: Send + Sync {
+    type Detected: Send + Sync;
 
-    async fn check(
-        self,
-    ) -> Result<CheckResult<Self::Previous, Self::Current, Self::External>, Error>;
+    async fn detect(&self) -> Result<Self::Detected, Error>;
 }
 
-pub async fn run_checker<C>(checker: C) -> CheckResult<C::Previous, C::Current, C::External>
+pub async fn run_checker<C>(checker: &C) -> Option<C::Detected>
 where
     C: IpChecker,
 {
-    match checker.check().await {
-        Ok(result) => result,
+    match checker.detect().await {
+        Ok(detected) => Some(detected),
         Err(err) => {
             error!("{}", err);
-            CheckResult::default()
+            None
         }
     }
 }
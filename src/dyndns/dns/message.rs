@@ -0,0 +1,383 @@
+use crate::Error;
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_NS: u16 = 2;
+pub const TYPE_SOA: u16 = 6;
+pub const TYPE_TXT: u16 = 16;
+pub const TYPE_AAAA: u16 = 28;
+pub const TYPE_TSIG: u16 = 250;
+
+pub const CLASS_IN: u16 = 1;
+pub const CLASS_ANY: u16 = 255;
+
+pub const OPCODE_QUERY: u16 = 0;
+pub const OPCODE_UPDATE: u16 = 5;
+
+const FLAG_QR: u16 = 0x8000;
+const FLAG_TC: u16 = 0x0200;
+const FLAG_RD: u16 = 0x0100;
+
+pub const HEADER_LEN: usize = 12;
+
+pub struct MessageBuilder {
+    buf: Vec<u8>,
+}
+
+impl MessageBuilder {
+    pub fn new(id: u16, opcode: u16) -> Self {
+        let mut buf = Vec::with_capacity(512);
+        buf.extend_from_slice(&id.to_be_bytes());
+        buf.extend_from_slice(&((opcode & 0x0f) << 11).to_be_bytes());
+        buf.extend_from_slice(&[0; 8]);
+        Self { buf }
+    }
+
+    pub fn recursion_desired(&mut self) -> &mut Self {
+        let flags = u16::from_be_bytes([self.buf[2], self.buf[3]]) | FLAG_RD;
+        self.buf[2..4].copy_from_slice(&flags.to_be_bytes());
+        self
+    }
+
+    /// Appends an entry to the first (question / zone) section.
+    pub fn question(&mut self, name: &str, rtype: u16, class: u16) -> Result<&mut Self, Error> {
+        encode_name(&mut self.buf, name)?;
+        self.buf.extend_from_slice(&rtype.to_be_bytes());
+        self.buf.extend_from_slice(&class.to_be_bytes());
+        self.bump(4);
+        Ok(self)
+    }
+
+    /// Appends a resource record to the update (authority) section.
+    pub fn update(
+        &mut self,
+        name: &str,
+        rtype: u16,
+        class: u16,
+        ttl: u32,
+        rdata: &[u8],
+    ) -> Result<&mut Self, Error> {
+        encode_name(&mut self.buf, name)?;
+        self.buf.extend_from_slice(&rtype.to_be_bytes());
+        self.buf.extend_from_slice(&class.to_be_bytes());
+        self.buf.extend_from_slice(&ttl.to_be_bytes());
+        self.buf
+            .extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        self.buf.extend_from_slice(rdata);
+        self.bump(8);
+        Ok(self)
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+
+    fn bump(&mut self, offset: usize) {
+        let count = u16::from_be_bytes([self.buf[offset], self.buf[offset + 1]]) + 1;
+        self.buf[offset..offset + 2].copy_from_slice(&count.to_be_bytes());
+    }
+}
+
+/// Increments the additional record count of an encoded message.
+pub fn bump_additional(message: &mut [u8]) {
+    let count = u16::from_be_bytes([message[10], message[11]]) + 1;
+    message[10..12].copy_from_slice(&count.to_be_bytes());
+}
+
+pub fn encode_name(buf: &mut Vec<u8>, name: &str) -> Result<(), Error> {
+    let name = name.trim_end_matches('.');
+    if !name.is_empty() {
+        for label in name.split('.') {
+            if label.is_empty() || label.len() > 63 {
+                return Err(Error::dns(format!("invalid domain name: {name}")));
+            }
+            buf.push(label.len() as u8);
+            buf.extend(label.bytes().map(|b| b.to_ascii_lowercase()));
+        }
+    }
+    buf.push(0);
+    Ok(())
+}
+
+/// A resource record from the answer section of a response.
+pub struct Record {
+    pub rtype: u16,
+    pub rdata: Vec<u8>,
+    /// Position of the record data in the message, for names compressed
+    /// against earlier parts of it.
+    pub offset: usize,
+}
+
+/// Parses the answer section of a response, skipping the question section.
+pub fn answers(message: &[u8]) -> Result<Vec<Record>, Error> {
+    if message.len() < HEADER_LEN {
+        return Err(Error::dns("truncated dns header"));
+    }
+    let questions = u16::from_be_bytes([message[4], message[5]]);
+    let answers = u16::from_be_bytes([message[6], message[7]]);
+
+    let mut offset = HEADER_LEN;
+    for _ in 0..questions {
+        offset = skip_name(message, offset)? + 4;
+    }
+    let mut records = Vec::with_capacity(answers as usize);
+    for _ in 0..answers {
+        offset = skip_name(message, offset)?;
+        let fixed = message
+            .get(offset..offset + 10)
+            .ok_or_else(|| Error::dns("truncated dns record"))?;
+        let rtype = u16::from_be_bytes([fixed[0], fixed[1]]);
+        let len = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+        offset += 10;
+        let rdata = message
+            .get(offset..offset + len)
+            .ok_or_else(|| Error::dns("truncated dns record"))?;
+        records.push(Record {
+            rtype,
+            rdata: rdata.to_vec(),
+            offset,
+        });
+        offset += len;
+    }
+    Ok(records)
+}
+
+/// Decodes the possibly compressed name at `offset`, e.g. the target of an NS record.
+pub fn read_name(message: &[u8], mut offset: usize) -> Result<String, Error> {
+    let mut labels = vec![];
+    // Every pointer must go backwards, which bounds the loop.
+    let mut limit = offset;
+    loop {
+        let len = *message
+            .get(offset)
+            .ok_or_else(|| Error::dns("truncated dns name"))?;
+        match len {
+            0 => return Ok(labels.join(".")),
+            len if len & 0xc0 == 0xc0 => {
+                let low = *message
+                    .get(offset + 1)
+                    .ok_or_else(|| Error::dns("truncated dns name"))?;
+                let target = (((len & 0x3f) as usize) << 8) | low as usize;
+                if target >= limit {
+                    return Err(Error::dns("invalid dns name pointer"));
+                }
+                limit = target;
+                offset = target;
+            }
+            len => {
+                let label = message
+                    .get(offset + 1..offset + 1 + len as usize)
+                    .ok_or_else(|| Error::dns("truncated dns name"))?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                offset += 1 + len as usize;
+            }
+        }
+    }
+}
+
+/// Finds the last record of the additional section, where a TSIG record
+/// has to be, and returns its offset.
+pub fn last_additional(message: &[u8]) -> Result<Option<usize>, Error> {
+    if message.len() < HEADER_LEN {
+        return Err(Error::dns("truncated dns header"));
+    }
+    let count = |at: usize| u16::from_be_bytes([message[at], message[at + 1]]) as usize;
+    if count(10) == 0 {
+        return Ok(None);
+    }
+
+    let mut offset = HEADER_LEN;
+    for _ in 0..count(4) {
+        offset = skip_name(message, offset)? + 4;
+    }
+    for _ in 1..count(6) + count(8) + count(10) {
+        offset = skip_name(message, offset)?;
+        let fixed = message
+            .get(offset..offset + 10)
+            .ok_or_else(|| Error::dns("truncated dns record"))?;
+        offset += 10 + u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+    }
+    if offset >= message.len() {
+        return Err(Error::dns("truncated dns record"));
+    }
+    Ok(Some(offset))
+}
+
+pub fn skip_name(message: &[u8], mut offset: usize) -> Result<usize, Error> {
+    loop {
+        let len = *message
+            .get(offset)
+            .ok_or_else(|| Error::dns("truncated dns name"))?;
+        match len {
+            0 => return Ok(offset + 1),
+            len if len & 0xc0 == 0xc0 => return Ok(offset + 2),
+            len => offset += 1 + len as usize,
+        }
+    }
+}
+
+pub struct Header {
+    pub id: u16,
+    flags: u16,
+}
+
+impl Header {
+    pub fn parse(message: &[u8]) -> Result<Self, Error> {
+        if message.len() < HEADER_LEN {
+            return Err(Error::dns("truncated dns header"));
+        }
+        Ok(Self {
+            id: u16::from_be_bytes([message[0], message[1]]),
+            flags: u16::from_be_bytes([message[2], message[3]]),
+        })
+    }
+
+    pub fn is_response(&self) -> bool {
+        self.flags & FLAG_QR != 0
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.flags & FLAG_TC != 0
+    }
+
+    pub fn rcode(&self) -> u16 {
+        self.flags & 0x000f
+    }
+}
+
+pub fn rcode_name(rcode: u16) -> &'static str {
+    match rcode {
+        0 => "NOERROR",
+        1 => "FORMERR",
+        2 => "SERVFAIL",
+        3 => "NXDOMAIN",
+        4 => "NOTIMP",
+        5 => "REFUSED",
+        6 => "YXDOMAIN",
+        7 => "YXRRSET",
+        8 => "NXRRSET",
+        9 => "NOTAUTH",
+        10 => "NOTZONE",
+        _ => "UNKNOWN",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A response to `www.example.com A` with a CNAME to `example.com` and
+    /// its A record, both owner names and the CNAME target compressed.
+    fn response() -> Vec<u8> {
+        let mut message = vec![0x12, 0x34, 0x81, 0x80, 0, 1, 0, 2, 0, 0, 0, 0];
+        message.extend_from_slice(b"\x03www\x07example\x03com\x00");
+        message.extend_from_slice(&[0, 1, 0, 1]);
+        // www.example.com CNAME example.com
+        message.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 2, 0xc0, 16]);
+        // example.com A 192.0.2.1
+        message.extend_from_slice(&[0xc0, 16, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 192, 0, 2, 1]);
+        message
+    }
+
+    #[test]
+    fn builds_question_and_update_sections() {
+        let mut builder = MessageBuilder::new(0xabcd, OPCODE_UPDATE);
+        builder
+            .question("Example.COM.", TYPE_SOA, CLASS_IN)
+            .unwrap();
+        builder
+            .update("host.example.com", TYPE_A, CLASS_IN, 300, &[192, 0, 2, 1])
+            .unwrap();
+        let message = builder.finish();
+
+        assert_eq!(&message[..4], &[0xab, 0xcd, 0x28, 0x00]);
+        assert_eq!(&message[4..12], &[0, 1, 0, 0, 0, 1, 0, 0]);
+        assert_eq!(&message[12..25], b"\x07example\x03com\x00");
+        assert_eq!(read_name(&message, 29).unwrap(), "host.example.com");
+    }
+
+    #[test]
+    fn rejects_invalid_labels() {
+        assert!(encode_name(&mut vec![], "a..b").is_err());
+        assert!(encode_name(&mut vec![], &"a".repeat(64)).is_err());
+        let mut root = vec![];
+        encode_name(&mut root, ".").unwrap();
+        assert_eq!(root, [0]);
+    }
+
+    #[test]
+    fn parses_answers_with_compressed_names() {
+        let message = response();
+        let records = answers(&message).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].rtype, 5);
+        assert_eq!(
+            read_name(&message, records[0].offset).unwrap(),
+            "example.com"
+        );
+        assert_eq!(records[1].rtype, TYPE_A);
+        assert_eq!(records[1].rdata, [192, 0, 2, 1]);
+    }
+
+    #[test]
+    fn follows_chained_pointers() {
+        let mut message = response();
+        let offset = message.len();
+        // mail -> www.example.com, which itself ends in a pointer.
+        message.extend_from_slice(b"\x04mail\xc0\x0c");
+        let chained = message.len();
+        message.extend_from_slice(&[0xc0, offset as u8]);
+        assert_eq!(
+            read_name(&message, chained).unwrap(),
+            "mail.www.example.com"
+        );
+    }
+
+    #[test]
+    fn rejects_pointer_loops() {
+        let mut message = response();
+        let offset = message.len();
+        // A pointer to itself, and one pointing forward.
+        message.extend_from_slice(&[0xc0, offset as u8]);
+        message.extend_from_slice(&[0xc0, offset as u8 + 4, 0]);
+        assert!(read_name(&message, offset).is_err());
+        assert!(read_name(&message, offset + 2).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_responses() {
+        let message = response();
+        for len in [4, 20, message.len() - 2] {
+            assert!(answers(&message[..len]).is_err(), "length {len}");
+        }
+        assert!(read_name(&message[..20], 12).is_err());
+    }
+
+    #[test]
+    fn reads_header_flags() {
+        let mut message = response();
+        let header = Header::parse(&message).unwrap();
+        assert_eq!(header.id, 0x1234);
+        assert!(header.is_response());
+        assert!(!header.is_truncated());
+        assert_eq!(header.rcode(), 0);
+
+        message[2] |= 0x02;
+        message[3] |= 0x03;
+        let header = Header::parse(&message).unwrap();
+        assert!(header.is_truncated());
+        assert_eq!(rcode_name(header.rcode()), "NXDOMAIN");
+        assert!(Header::parse(&message[..11]).is_err());
+    }
+
+    #[test]
+    fn finds_last_additional_record() {
+        let mut message = response();
+        assert_eq!(last_additional(&message).unwrap(), None);
+
+        let end = message.len();
+        message[11] = 1;
+        message.extend_from_slice(&[0, 0, 41, 4, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(last_additional(&message).unwrap(), Some(end));
+        assert!(last_additional(&message[..end]).is_err());
+    }
+}
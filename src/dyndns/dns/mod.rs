@@ -0,0 +1,6 @@
+pub mod message;
+mod transport;
+mod tsig;
+
+pub use transport::{exchange, resolve_server};
+pub use tsig::TsigKey;
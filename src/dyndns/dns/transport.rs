@@ -0,0 +1,118 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpSocket, UdpSocket, lookup_host},
+    time::timeout,
+};
+
+use crate::Error;
+
+use super::message::Header;
+
+const DNS_PORT: u16 = 53;
+const MAX_UDP_PAYLOAD: usize = 512;
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Resolves `host`, `host:port`, `ip` or `[ipv6]:port` to a socket address.
+pub async fn resolve_server(server: &str) -> Result<SocketAddr, Error> {
+    if let Ok(addr) = server.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    if let Ok(ip) = server
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        return Ok(SocketAddr::new(ip, DNS_PORT));
+    }
+    let target = if server.contains(':') {
+        server.to_string()
+    } else {
+        format!("{server}:{DNS_PORT}")
+    };
+    lookup_host(target)
+        .await?
+        .next()
+        .ok_or_else(|| Error::dns(format!("failed to resolve dns server {server}")))
+}
+
+/// Sends a message over UDP and retries over TCP when the reply is truncated,
+/// the message is too large for UDP or the UDP exchange fails.
+/// When `interface` is set the sockets are bound to that device.
+pub async fn exchange(
+    server: SocketAddr,
+    interface: Option<&str>,
+    message: &[u8],
+) -> Result<Vec<u8>, Error> {
+    if message.len() <= MAX_UDP_PAYLOAD {
+        match exchange_udp(server, interface, message).await {
+            Ok(response) if !Header::parse(&response)?.is_truncated() => return Ok(response),
+            Ok(_) => debug!("dns response from {} truncated, retrying over tcp", server),
+            Err(err) => warn!("dns udp exchange with {} failed: {}", server, err),
+        }
+    }
+    exchange_tcp(server, interface, message).await
+}
+
+async fn exchange_udp(
+    server: SocketAddr,
+    interface: Option<&str>,
+    message: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let bind: SocketAddr = match server {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    if let Some(interface) = interface {
+        socket.bind_device(Some(interface.as_bytes()))?;
+    }
+    socket.connect(server).await?;
+    socket.send(message).await?;
+
+    let mut buf = vec![0u8; 65_535];
+    loop {
+        let len = timeout(TIMEOUT, socket.recv(&mut buf))
+            .await
+            .map_err(|_| Error::dns(format!("dns query to {server} timed out")))??;
+        let response = &buf[..len];
+        if response.len() >= 2 && response[..2] == message[..2] {
+            return Ok(response.to_vec());
+        }
+        debug!("ignoring dns response with mismatched id from {}", server);
+    }
+}
+
+async fn exchange_tcp(
+    server: SocketAddr,
+    interface: Option<&str>,
+    message: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let exchange = async {
+        let socket = match server {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        if let Some(interface) = interface {
+            socket.bind_device(Some(interface.as_bytes()))?;
+        }
+        let mut stream = socket.connect(server).await?;
+        stream
+            .write_all(&(message.len() as u16).to_be_bytes())
+            .await?;
+        stream.write_all(message).await?;
+
+        let len = stream.read_u16().await? as usize;
+        let mut response = vec![0u8; len];
+        stream.read_exact(&mut response).await?;
+        Ok::<_, std::io::Error>(response)
+    };
+    timeout(TIMEOUT, exchange)
+        .await
+        .map_err(|_| Error::dns(format!("dns query to {server} timed out")))?
+        .map_err(Error::from)
+}
@@ -0,0 +1,422 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac, digest::KeyInit};
+use sha2::{Sha256, Sha512};
+
+use crate::{Error, db::TsigAlgorithm};
+
+use super::message::{
+    CLASS_ANY, TYPE_TSIG, bump_additional, encode_name, last_additional, read_name, skip_name,
+};
+
+const FUDGE: u16 = 300;
+
+pub struct TsigKey {
+    name: String,
+    algorithm: TsigAlgorithm,
+    secret: Vec<u8>,
+}
+
+/// Why the TSIG record of a response was not accepted.
+#[derive(Debug, thiserror::Error)]
+pub enum TsigError {
+    #[error("server rejected the signature with {}", error_name(*.0))]
+    Rejected(u16),
+    #[error("response signed {0}s away from the local clock")]
+    BadTime(i64),
+    #[error("response is not signed")]
+    Unsigned,
+    #[error("response signature does not verify")]
+    BadSignature,
+    #[error(transparent)]
+    Malformed(#[from] Error),
+}
+
+impl TsigError {
+    /// Key, algorithm or clock problems that retrying will not fix.
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, Self::Rejected(_) | Self::BadTime(_))
+    }
+}
+
+impl TsigKey {
+    pub fn new(name: impl Into<String>, algorithm: TsigAlgorithm, secret: Vec<u8>) -> Self {
+        Self {
+            name: name.into(),
+            algorithm,
+            secret,
+        }
+    }
+
+    /// Signs an encoded message in place by appending a TSIG record (RFC 8945),
+    /// and returns the MAC that the response signature has to cover.
+    pub fn sign(&self, message: &mut Vec<u8>) -> Result<Vec<u8>, Error> {
+        self.append(message, None, Utc::now().timestamp() as u64, 0)
+    }
+
+    /// Checks the TSIG record of a response to a request that was signed with
+    /// `request_mac` (RFC 8945 section 5.3).
+    pub fn verify(&self, response: &[u8], request_mac: &[u8]) -> Result<(), TsigError> {
+        let start = last_additional(response)?.ok_or(TsigError::Unsigned)?;
+        let mut offset = skip_name(response, start)?;
+        let fixed = response
+            .get(offset..offset + 10)
+            .ok_or_else(|| Error::dns("truncated tsig record"))?;
+        if u16::from_be_bytes([fixed[0], fixed[1]]) != TYPE_TSIG {
+            return Err(TsigError::Unsigned);
+        }
+        offset += 10;
+        let end = offset + u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+
+        if !same_name(&read_name(response, start)?, &self.name)
+            || !same_name(&read_name(response, offset)?, self.algorithm.dns_name())
+        {
+            return Err(TsigError::BadSignature);
+        }
+        let mut fields = Fields {
+            data: response
+                .get(skip_name(response, offset)?..end)
+                .ok_or_else(|| Error::dns("truncated tsig record"))?,
+        };
+        let time = fields.take(6)?;
+        let fudge = fields.u16()?;
+        let mac_len = fields.u16()? as usize;
+        let mac = fields.take(mac_len)?;
+        let original_id = fields.take(2)?;
+        let error = fields.u16()?;
+        let other_len = fields.u16()? as usize;
+        let other = fields.take(other_len)?;
+
+        if error != 0 {
+            return Err(TsigError::Rejected(error));
+        }
+        if mac.is_empty() {
+            return Err(TsigError::Unsigned);
+        }
+
+        let mut digest_input = Vec::with_capacity(end + request_mac.len());
+        digest_input.extend_from_slice(&(request_mac.len() as u16).to_be_bytes());
+        digest_input.extend_from_slice(request_mac);
+        digest_input.extend_from_slice(&response[..start]);
+        digest_input[2 + request_mac.len()..][..2].copy_from_slice(original_id);
+        let additional = &mut digest_input[2 + request_mac.len() + 10..][..2];
+        let count = u16::from_be_bytes([additional[0], additional[1]]) - 1;
+        additional.copy_from_slice(&count.to_be_bytes());
+        let mut time_signed = [0; 8];
+        time_signed[2..].copy_from_slice(time);
+        let time_signed = u64::from_be_bytes(time_signed);
+        digest_input.extend(self.variables(time_signed, fudge, error, other)?);
+
+        if !verify_mac(self.algorithm, &self.secret, &digest_input, mac) {
+            return Err(TsigError::BadSignature);
+        }
+        let skew = Utc::now().timestamp() - time_signed as i64;
+        if skew.unsigned_abs() > fudge as u64 {
+            return Err(TsigError::BadTime(skew));
+        }
+        Ok(())
+    }
+
+    /// Signs a response to `request` the way a server does, for tests that
+    /// stand in for one.
+    #[cfg(test)]
+    pub fn sign_response(&self, response: &mut Vec<u8>, request: &[u8]) -> Result<(), Error> {
+        let start = last_additional(request)?.ok_or_else(|| Error::dns("request is not signed"))?;
+        let algorithm = skip_name(request, start)? + 10;
+        let mut fields = Fields {
+            data: &request[skip_name(request, algorithm)?..],
+        };
+        fields.take(8)?;
+        let mac_len = fields.u16()? as usize;
+        let mac = fields.take(mac_len)?;
+        self.append(response, Some(mac), Utc::now().timestamp() as u64, 0)?;
+        Ok(())
+    }
+
+    /// Appends a TSIG record covering `message` and, for responses, the MAC
+    /// of the request.
+    fn append(
+        &self,
+        message: &mut Vec<u8>,
+        request_mac: Option<&[u8]>,
+        time_signed: u64,
+        error: u16,
+    ) -> Result<Vec<u8>, Error> {
+        let original_id = [message[0], message[1]];
+
+        let mut digest_input = vec![];
+        if let Some(request_mac) = request_mac {
+            digest_input.extend_from_slice(&(request_mac.len() as u16).to_be_bytes());
+            digest_input.extend_from_slice(request_mac);
+        }
+        digest_input.extend_from_slice(message);
+        digest_input.extend(self.variables(time_signed, FUDGE, error, &[])?);
+        let mac = mac(self.algorithm, &self.secret, &digest_input);
+
+        let mut key_name = Vec::new();
+        encode_name(&mut key_name, &self.name)?;
+        let mut rdata = Vec::new();
+        encode_name(&mut rdata, self.algorithm.dns_name())?;
+        rdata.extend_from_slice(&time_signed.to_be_bytes()[2..]);
+        rdata.extend_from_slice(&FUDGE.to_be_bytes());
+        rdata.extend_from_slice(&(mac.len() as u16).to_be_bytes());
+        rdata.extend_from_slice(&mac);
+        rdata.extend_from_slice(&original_id);
+        rdata.extend_from_slice(&error.to_be_bytes());
+        rdata.extend_from_slice(&0u16.to_be_bytes());
+
+        message.extend_from_slice(&key_name);
+        message.extend_from_slice(&TYPE_TSIG.to_be_bytes());
+        message.extend_from_slice(&CLASS_ANY.to_be_bytes());
+        message.extend_from_slice(&0u32.to_be_bytes());
+        message.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        message.extend_from_slice(&rdata);
+        bump_additional(message);
+        Ok(mac)
+    }
+
+    /// The TSIG variables that are digested after the message.
+    fn variables(
+        &self,
+        time_signed: u64,
+        fudge: u16,
+        error: u16,
+        other: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let mut variables = Vec::new();
+        encode_name(&mut variables, &self.name)?;
+        variables.extend_from_slice(&CLASS_ANY.to_be_bytes());
+        variables.extend_from_slice(&0u32.to_be_bytes());
+        encode_name(&mut variables, self.algorithm.dns_name())?;
+        variables.extend_from_slice(&time_signed.to_be_bytes()[2..]);
+        variables.extend_from_slice(&fudge.to_be_bytes());
+        variables.extend_from_slice(&error.to_be_bytes());
+        variables.extend_from_slice(&(other.len() as u16).to_be_bytes());
+        variables.extend_from_slice(other);
+        Ok(variables)
+    }
+}
+
+struct Fields<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.data.len() < len {
+            return Err(Error::dns("truncated tsig record"));
+        }
+        let (field, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(field)
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        self.take(2)
+            .map(|field| u16::from_be_bytes([field[0], field[1]]))
+    }
+}
+
+fn same_name(name: &str, expected: &str) -> bool {
+    name.trim_end_matches('.')
+        .eq_ignore_ascii_case(expected.trim_end_matches('.'))
+}
+
+fn error_name(error: u16) -> &'static str {
+    match error {
+        16 => "BADSIG",
+        17 => "BADKEY",
+        18 => "BADTIME",
+        19 => "BADMODE",
+        20 => "BADNAME",
+        21 => "BADALG",
+        22 => "BADTRUNC",
+        _ => "UNKNOWN",
+    }
+}
+
+fn mac(algorithm: TsigAlgorithm, secret: &[u8], data: &[u8]) -> Vec<u8> {
+    match algorithm {
+        TsigAlgorithm::HmacSha256 => keyed::<Hmac<Sha256>>(secret, data)
+            .finalize()
+            .into_bytes()
+            .to_vec(),
+        TsigAlgorithm::HmacSha512 => keyed::<Hmac<Sha512>>(secret, data)
+            .finalize()
+            .into_bytes()
+            .to_vec(),
+    }
+}
+
+/// Compares in constant time.
+fn verify_mac(algorithm: TsigAlgorithm, secret: &[u8], data: &[u8], expected: &[u8]) -> bool {
+    match algorithm {
+        TsigAlgorithm::HmacSha256 => keyed::<Hmac<Sha256>>(secret, data)
+            .verify_slice(expected)
+            .is_ok(),
+        TsigAlgorithm::HmacSha512 => keyed::<Hmac<Sha512>>(secret, data)
+            .verify_slice(expected)
+            .is_ok(),
+    }
+}
+
+fn keyed<M: Mac + KeyInit>(secret: &[u8], data: &[u8]) -> M {
+    // HMAC takes keys of any length, so this cannot fail.
+    let mut mac = <M as Mac>::new_from_slice(secret).unwrap();
+    mac.update(data);
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::message::{CLASS_IN, HEADER_LEN, MessageBuilder, OPCODE_UPDATE, TYPE_SOA};
+    use super::*;
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // RFC 4231 section 4.
+    const VECTORS: &[(&[u8], &[u8], &str, &str)] = &[
+        (
+            &[0x0b; 20],
+            b"Hi There",
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            "87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cde\
+             daa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854",
+        ),
+        (
+            b"Jefe",
+            b"what do ya want for nothing?",
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea250554\
+             9758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737",
+        ),
+        (
+            &[0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First",
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            "80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f352\
+             6b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598",
+        ),
+    ];
+
+    fn key() -> TsigKey {
+        TsigKey::new("update-key.", TsigAlgorithm::HmacSha256, b"secret".to_vec())
+    }
+
+    /// A signed update request and the MAC it was signed with.
+    fn request(key: &TsigKey) -> (Vec<u8>, Vec<u8>) {
+        let mut builder = MessageBuilder::new(0x1234, OPCODE_UPDATE);
+        builder.question("example.com", TYPE_SOA, CLASS_IN).unwrap();
+        let mut message = builder.finish();
+        let mac = key.sign(&mut message).unwrap();
+        (message, mac)
+    }
+
+    fn response(
+        key: &TsigKey,
+        request_mac: Option<&[u8]>,
+        time_signed: i64,
+        error: u16,
+    ) -> Vec<u8> {
+        let mut builder = MessageBuilder::new(0x1234, OPCODE_UPDATE);
+        builder.question("example.com", TYPE_SOA, CLASS_IN).unwrap();
+        let mut message = builder.finish();
+        message[2] |= 0x80;
+        key.append(&mut message, request_mac, time_signed as u64, error)
+            .unwrap();
+        message
+    }
+
+    #[test]
+    fn verifies_response_chained_to_request() {
+        let key = key();
+        let (_, mac) = request(&key);
+        let response = response(&key, Some(&mac), Utc::now().timestamp(), 0);
+        assert!(key.verify(&response, &mac).is_ok());
+    }
+
+    #[test]
+    fn rejects_response_not_chained_to_request() {
+        let key = key();
+        let (_, mac) = request(&key);
+        let response = response(&key, None, Utc::now().timestamp(), 0);
+        assert!(matches!(
+            key.verify(&response, &mac),
+            Err(TsigError::BadSignature)
+        ));
+    }
+
+    #[test]
+    fn rejects_tampered_response() {
+        let key = key();
+        let (_, mac) = request(&key);
+        let mut response = response(&key, Some(&mac), Utc::now().timestamp(), 0);
+        // A letter of the zone name in the question.
+        response[HEADER_LEN + 10] ^= 0x01;
+        assert!(matches!(
+            key.verify(&response, &mac),
+            Err(TsigError::BadSignature)
+        ));
+    }
+
+    #[test]
+    fn rejects_response_signed_with_other_key() {
+        let (_, mac) = request(&key());
+        let other = TsigKey::new("update-key.", TsigAlgorithm::HmacSha256, b"other".to_vec());
+        let response = response(&other, Some(&mac), Utc::now().timestamp(), 0);
+        assert!(matches!(
+            key().verify(&response, &mac),
+            Err(TsigError::BadSignature)
+        ));
+    }
+
+    #[test]
+    fn reports_server_tsig_errors_as_fatal() {
+        let key = key();
+        let (_, mac) = request(&key);
+        let response = response(&key, Some(&mac), Utc::now().timestamp(), 17);
+        let err = key.verify(&response, &mac).unwrap_err();
+        assert!(matches!(err, TsigError::Rejected(17)));
+        assert!(err.is_fatal());
+        assert_eq!(err.to_string(), "server rejected the signature with BADKEY");
+    }
+
+    #[test]
+    fn reports_clock_skew_as_fatal() {
+        let key = key();
+        let (_, mac) = request(&key);
+        let response = response(&key, Some(&mac), Utc::now().timestamp() - 3600, 0);
+        let err = key.verify(&response, &mac).unwrap_err();
+        assert!(matches!(err, TsigError::BadTime(skew) if skew >= 3600));
+        assert!(err.is_fatal());
+    }
+
+    #[test]
+    fn rejects_unsigned_response() {
+        let key = key();
+        let (_, mac) = request(&key);
+        let mut builder = MessageBuilder::new(0x1234, OPCODE_UPDATE);
+        builder.question("example.com", TYPE_SOA, CLASS_IN).unwrap();
+        let err = key.verify(&builder.finish(), &mac).unwrap_err();
+        assert!(matches!(err, TsigError::Unsigned));
+        assert!(!err.is_fatal());
+    }
+
+    #[test]
+    fn hmac_sha256_matches_rfc4231() {
+        for (key, data, expected, _) in VECTORS {
+            assert_eq!(mac(TsigAlgorithm::HmacSha256, key, data), hex(expected));
+        }
+    }
+
+    #[test]
+    fn hmac_sha512_matches_rfc4231() {
+        for (key, data, _, expected) in VECTORS {
+            assert_eq!(mac(TsigAlgorithm::HmacSha512, key, data), hex(expected));
+        }
+    }
+}
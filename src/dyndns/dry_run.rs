@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::{
+    Error,
+    db::{AttemptResult, DynDNS, IpVersion, Settings},
+};
+
+use super::{
+    checker::{
+        IpChecker,
+        ipv4::Ipv4CheckResult,
+        ipv6::{Ipv6CheckResult, Ipv6Suffix},
+    },
+    http_client::HttpClient,
+    provider::{DnsProvider, Provider},
+    scheduler::Detection,
+    trace::{Trace, TraceEntry},
+    updater::DynDnsUpdater,
+};
+
+/// Outcome of testing a configuration that has not been saved.
+#[derive(Debug, Serialize)]
+pub struct DryRunReport {
+    pub success: bool,
+    pub ipv4: Option<String>,
+    pub ipv6: Option<String>,
+    pub warnings: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probe: Option<ProbeReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update: Option<UpdateReport>,
+    pub errors: Vec<String>,
+    pub trace: Vec<TraceEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProbeReport {
+    /// Whether the protocol has a call that changes nothing.
+    pub supported: bool,
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UpdateReport {
+    pub result: AttemptResult,
+    pub response: Option<String>,
+}
+
+/// Detects the addresses for `config` and checks it against the provider
+/// without touching the database. Records are only changed when `update` is
+/// set; otherwise the provider is probed where its protocol allows.
+pub async fn dry_run(config: &DynDNS, settings: &Settings, update: bool) -> DryRunReport {
+    let trace = Trace::new(&config.password);
+    let client = HttpClient::new(1, Duration::ZERO).with_trace(trace.clone());
+    let mut report = DryRunReport {
+        success: false,
+        ipv4: None,
+        ipv6: None,
+        warnings: vec![],
+        probe: None,
+        update: None,
+        errors: vec![],
+        trace: vec![],
+    };
+
+    let (ipv4, ipv6) = detect(&client, config, settings, &mut report).await;
+    report.ipv4 = ipv4.summary();
+    report.ipv6 = ipv6.summary();
+
+    match check_provider(&client, config, &ipv4, &ipv6, update).await {
+        Ok((probe, outcome)) => {
+            report.success = report.errors.is_empty()
+                && outcome
+                    .as_ref()
+                    .is_none_or(|outcome| outcome.result.is_success());
+            report.probe = probe;
+            report.update = outcome;
+        }
+        Err(err) => report.errors.push(err.to_string()),
+    }
+
+    for error in report.errors.iter_mut() {
+        trace.redact(error);
+    }
+    if let Some(response) = report
+        .update
+        .as_mut()
+        .and_then(|update| update.response.as_mut())
+    {
+        trace.redact(response);
+    }
+    report.trace = trace.take();
+    report
+}
+
+/// Runs detection as a forced update would, collecting failures instead of
+/// only logging them.
+async fn detect(
+    client: &HttpClient,
+    config: &DynDNS,
+    settings: &Settings,
+    report: &mut DryRunReport,
+) -> (Ipv4CheckResult, Ipv6CheckResult) {
+    let detection = Detection::new(client, &config.interface, settings);
+    let mut ipv4_result = Ipv4CheckResult::default();
+    let mut ipv6_result = Ipv6CheckResult::default();
+
+    if matches!(config.ip, IpVersion::V4 | IpVersion::All) {
+        match detection.ipv4_checker.detect().await {
+            Ok(answer) => ipv4_result = detection.ipv4_checker.compare(None, &answer, true),
+            Err(err) => report.errors.push(format!("ipv4 detection: {err}")),
+        }
+    }
+    if matches!(config.ip, IpVersion::V6 | IpVersion::All) {
+        match detection.ipv6_checker.detect().await {
+            Ok(addresses) => {
+                let suffix = Ipv6Suffix::from_config(config);
+                let publish_all = config.options.publish_all_ipv6.unwrap_or(false);
+                ipv6_result = detection
+                    .ipv6_checker
+                    .compare_published(vec![], &addresses, suffix.as_ref(), true, publish_all)
+                    .await;
+            }
+            Err(err) => report.errors.push(format!("ipv6 detection: {err}")),
+        }
+    }
+
+    report.warnings = detection.take_warnings();
+    (ipv4_result, ipv6_result)
+}
+
+async fn check_provider(
+    client: &HttpClient,
+    config: &DynDNS,
+    ipv4: &Ipv4CheckResult,
+    ipv6: &Ipv6CheckResult,
+    update: bool,
+) -> Result<(Option<ProbeReport>, Option<UpdateReport>), Error> {
+    if !update {
+        let detail = Provider::new(client, config)?.probe().await?;
+        let probe = ProbeReport {
+            supported: detail.is_some(),
+            detail,
+        };
+        return Ok((Some(probe), None));
+    }
+
+    let Some(outcome) = DynDnsUpdater::new(client, config)?
+        .apply(ipv4, ipv6)
+        .await?
+    else {
+        return Err(match config.ip {
+            IpVersion::V6 => Error::ipv6_not_found(),
+            IpVersion::V4 | IpVersion::All => Error::ipv4_not_found(),
+        });
+    };
+    let (result, response) = outcome.into_attempt();
+    Ok((None, Some(UpdateReport { result, response })))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Method;
+    use serde_json::json;
+
+    use super::*;
+    use crate::dyndns::provider::mock::{MockServer, Reply};
+
+    #[tokio::test]
+    async fn reports_update_of_spaced_hostname_list() {
+        let server = MockServer::start(vec![Reply::text(Method::GET, "/ip", "203.0.113.7")]).await;
+        let settings: Settings = serde_json::from_value(json!({
+            "sleep_interval": 300,
+            "ipv4_lookup": [{ "url": format!("{}/ip", server.url) }],
+        }))
+        .unwrap();
+        let config: DynDNS = serde_json::from_value(json!({
+            "server": "127.0.0.1:1",
+            "username": "user",
+            "password": "password",
+            "hostname": "a.example.com, b.example.com",
+            "ip": 1,
+            "interface": "lo",
+        }))
+        .unwrap();
+
+        let report = dry_run(&config, &settings, true).await;
+        assert!(report.ipv4.unwrap().starts_with("203.0.113.7 "));
+        assert!(!report.success);
+        assert_eq!(report.errors.len(), 1, "{:?}", report.errors);
+    }
+}
@@ -1,14 +1,20 @@
-use std::time::Duration;
+use std::{collections::BTreeMap, time::Duration};
 
-use isahc::{AsyncBody, Request, RequestExt, Response, config::Configurable};
+use isahc::{
+    AsyncBody, Request, RequestExt, Response, config::Configurable, http::HeaderMap,
+    prelude::AsyncReadResponseExt,
+};
 
 use crate::Error;
 
+use super::trace::{HttpResponseTrace, Trace, TraceEntry};
+
 #[derive(Clone)]
 pub(crate) struct HttpClient {
     inner: isahc::HttpClient,
     max_attempts: usize,
     initial_delay: Duration,
+    trace: Option<Trace>,
 }
 
 impl HttpClient {
@@ -26,12 +32,23 @@ impl HttpClient {
             inner,
             max_attempts: max_attempts.max(1),
             initial_delay,
+            trace: None,
         }
     }
 
+    /// Records every exchange, responses are buffered to do so.
+    pub fn with_trace(mut self, trace: Trace) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
     pub async fn send_async<B>(&self, request: Request<B>) -> Result<Response<AsyncBody>, Error>
     where
-        B: Into<AsyncBody> + Clone + Send + Sync + 'static,
+        B: Into<AsyncBody> + TraceBody + Clone + Send + Sync + 'static,
     {
         let mut delay = self.initial_delay;
         let body = request.body().clone();
@@ -40,10 +57,9 @@ impl HttpClient {
             let builder = request.to_builder();
             let current_request = builder.body(body.clone()).unwrap();
 
-            match self.inner.send_async(current_request).await {
+            match self.send_once(current_request).await {
                 Ok(response) => return Ok(response),
                 Err(err) => {
-                    let err: Error = err.into();
                     warn!(
                         "http client attempt {}/{} failed: {}",
                         attempt, self.max_attempts, err
@@ -56,10 +72,83 @@ impl HttpClient {
                 }
             }
         }
-        self.inner.send_async(request).await.map_err(|err| {
-            let err: Error = err.into();
+        self.send_once(request).await.map_err(|err| {
             error!("http client retries exhausted: {}", err);
             err
         })
     }
+
+    async fn send_once<B>(&self, request: Request<B>) -> Result<Response<AsyncBody>, Error>
+    where
+        B: Into<AsyncBody> + TraceBody,
+    {
+        let Some(trace) = self.trace.as_ref() else {
+            return Ok(self.inner.send_async(request).await?);
+        };
+
+        let method = request.method().to_string();
+        let url = request.uri().to_string();
+        let headers = header_map(request.headers());
+        let body = request.body().trace_body();
+
+        let exchange = async {
+            let mut response = self.inner.send_async(request).await?;
+            let bytes = response.bytes().await?;
+            Ok::<_, Error>((response, bytes))
+        };
+        let (result, response, error) = match exchange.await {
+            Ok((mut response, bytes)) => {
+                let traced = HttpResponseTrace {
+                    status: response.status().as_u16(),
+                    headers: header_map(response.headers()),
+                    body: String::from_utf8_lossy(&bytes).into_owned(),
+                };
+                *response.body_mut() = AsyncBody::from(bytes);
+                (Ok(response), Some(traced), None)
+            }
+            Err(err) => {
+                let message = err.to_string();
+                (Err(err), None, Some(message))
+            }
+        };
+
+        trace.record(TraceEntry::Http {
+            method,
+            url,
+            headers,
+            body,
+            response,
+            error,
+        });
+        result
+    }
+}
+
+/// Request bodies the trace can show.
+pub(crate) trait TraceBody {
+    fn trace_body(&self) -> Option<String>;
+}
+
+impl TraceBody for () {
+    fn trace_body(&self) -> Option<String> {
+        None
+    }
+}
+
+impl TraceBody for Vec<u8> {
+    fn trace_body(&self) -> Option<String> {
+        (!self.is_empty()).then(|| String::from_utf8_lossy(self).into_owned())
+    }
+}
+
+fn header_map(headers: &HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })
+        .collect()
 }
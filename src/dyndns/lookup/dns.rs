@@ -0,0 +1,250 @@
+use std::{
+    marker::PhantomData,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use rand_core::{OsRng, RngCore};
+
+use crate::{
+    Error,
+    db::{DnsLookupTarget, DnsRecordType},
+};
+
+use super::super::dns::{
+    exchange,
+    message::{
+        CLASS_IN, Header, MessageBuilder, OPCODE_QUERY, TYPE_A, TYPE_AAAA, TYPE_TXT, answers,
+        rcode_name,
+    },
+    resolve_server,
+};
+use super::{AddressFamily, IpLookup, IpLookupParser};
+
+/// Asks a resolver for the address it sees, e.g. `myip.opendns.com` against
+/// resolver1.opendns.com or `o-o.myaddr.l.google.com` TXT against ns1.google.com.
+pub struct DnsIpLookup<'a, P, T> {
+    interface: &'a str,
+    url: &'a str,
+    target: DnsLookupTarget,
+    parser: P,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, P, T> DnsIpLookup<'a, P, T> {
+    pub fn new(interface: &'a str, url: &'a str, target: DnsLookupTarget, parser: P) -> Self {
+        Self {
+            interface,
+            url,
+            target,
+            parser,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn url(&self) -> &'a str {
+        self.url
+    }
+}
+
+impl<'a, T, P> IpLookup<T> for DnsIpLookup<'a, P, T>
+where
+    T: AddressFamily + Send + Sync + 'a,
+    P: IpLookupParser<T> + Send + Sync,
+{
+    async fn lookup(&self) -> Result<T, Error> {
+        let rtype = match self.target.record_type {
+            Some(DnsRecordType::A) => TYPE_A,
+            Some(DnsRecordType::Aaaa) => TYPE_AAAA,
+            Some(DnsRecordType::Txt) => TYPE_TXT,
+            None => T::RECORD_TYPE,
+        };
+        let server = resolve_server(&self.target.resolver).await?;
+
+        let mut builder = MessageBuilder::new(OsRng.next_u32() as u16, OPCODE_QUERY);
+        builder
+            .recursion_desired()
+            .question(&self.target.name, rtype, CLASS_IN)?;
+        let response = exchange(server, Some(self.interface), &builder.finish()).await?;
+
+        let header = Header::parse(&response)?;
+        if header.rcode() != 0 {
+            return Err(Error::dns(format!(
+                "{} query for {} failed: {}",
+                server,
+                self.target.name,
+                rcode_name(header.rcode())
+            )));
+        }
+
+        let mut last_error = None;
+        for record in answers(&response)? {
+            if record.rtype != rtype {
+                continue;
+            }
+            match self.parser.parse(&record_text(rtype, &record.rdata)) {
+                Ok(address) => return Ok(address),
+                Err(err) => last_error = Some(err),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            Error::dns(format!(
+                "{} returned no matching record for {}",
+                server, self.target.name
+            ))
+        }))
+    }
+}
+
+/// Renders record data as text so the configured parser can extract the address.
+fn record_text(rtype: u16, rdata: &[u8]) -> String {
+    match rtype {
+        TYPE_A => match <[u8; 4]>::try_from(rdata) {
+            Ok(octets) => Ipv4Addr::from(octets).to_string(),
+            Err(_) => String::new(),
+        },
+        TYPE_AAAA => match <[u8; 16]>::try_from(rdata) {
+            Ok(octets) => Ipv6Addr::from(octets).to_string(),
+            Err(_) => String::new(),
+        },
+        _ => {
+            let mut text = String::new();
+            let mut rest = rdata;
+            while let Some((&len, tail)) = rest.split_first() {
+                let len = (len as usize).min(tail.len());
+                text.push_str(&String::from_utf8_lossy(&tail[..len]));
+                rest = &tail[len..];
+            }
+            text
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use tokio::net::UdpSocket;
+
+    use super::super::super::dns::message::{HEADER_LEN, read_name};
+    use super::super::parser::PlainTextIpParser;
+    use super::*;
+
+    /// Answers the OpenDNS and Google "what is my address" queries like the
+    /// real resolvers do, with no data for other types of those names and
+    /// NXDOMAIN for anything else.
+    async fn resolver() -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0; 512];
+            loop {
+                let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+                let query = &buf[..len];
+                let name = read_name(query, HEADER_LEN).unwrap();
+                let question_end = HEADER_LEN + name.len() + 2 + 4;
+                let rtype = u16::from_be_bytes([query[question_end - 4], query[question_end - 3]]);
+
+                let rdata = match (name.as_str(), rtype) {
+                    ("myip.opendns.com", TYPE_A) => Some(vec![203, 0, 113, 7]),
+                    ("o-o.myaddr.l.google.com", TYPE_TXT) => {
+                        let text = b"203.0.113.8";
+                        let mut rdata = vec![text.len() as u8];
+                        rdata.extend_from_slice(text);
+                        Some(rdata)
+                    }
+                    _ => None,
+                };
+
+                let mut response = query[..question_end].to_vec();
+                let known = matches!(
+                    name.as_str(),
+                    "myip.opendns.com" | "o-o.myaddr.l.google.com"
+                );
+                let rcode = if known { 0 } else { 3 };
+                response[2..4].copy_from_slice(&(0x8180u16 | rcode).to_be_bytes());
+                if let Some(rdata) = rdata {
+                    response[6..8].copy_from_slice(&1u16.to_be_bytes());
+                    // The answer refers back to the question name.
+                    response.extend_from_slice(&[0xc0, HEADER_LEN as u8]);
+                    response.extend_from_slice(&rtype.to_be_bytes());
+                    response.extend_from_slice(&CLASS_IN.to_be_bytes());
+                    response.extend_from_slice(&60u32.to_be_bytes());
+                    response.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+                    response.extend_from_slice(&rdata);
+                }
+                socket.send_to(&response, peer).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    async fn lookup<T>(
+        resolver: SocketAddr,
+        name: &str,
+        record_type: Option<DnsRecordType>,
+    ) -> Result<T, Error>
+    where
+        T: AddressFamily + Send + Sync,
+        PlainTextIpParser: IpLookupParser<T>,
+    {
+        let target = DnsLookupTarget {
+            resolver: resolver.to_string(),
+            name: name.to_owned(),
+            record_type,
+        };
+        DnsIpLookup::new("lo", "", target, PlainTextIpParser)
+            .lookup()
+            .await
+    }
+
+    #[tokio::test]
+    async fn reads_address_from_a_record() {
+        let resolver = resolver().await;
+        let address: Ipv4Addr = lookup(resolver, "myip.opendns.com", None).await.unwrap();
+        assert_eq!(address, Ipv4Addr::new(203, 0, 113, 7));
+    }
+
+    #[tokio::test]
+    async fn reads_address_from_txt_record() {
+        let resolver = resolver().await;
+        let address: Ipv4Addr = lookup(
+            resolver,
+            "o-o.myaddr.l.google.com",
+            Some(DnsRecordType::Txt),
+        )
+        .await
+        .unwrap();
+        assert_eq!(address, Ipv4Addr::new(203, 0, 113, 8));
+    }
+
+    #[tokio::test]
+    async fn reports_error_rcode() {
+        let resolver = resolver().await;
+        let err = lookup::<Ipv4Addr>(resolver, "example.com", None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().ends_with("failed: NXDOMAIN"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn reports_missing_record_of_queried_type() {
+        let resolver = resolver().await;
+        // The resolver only has an A record for this name.
+        let err = lookup::<Ipv6Addr>(resolver, "myip.opendns.com", None)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("returned no matching record"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn renders_txt_strings_as_one_text() {
+        assert_eq!(record_text(TYPE_TXT, b"\x03abc\x02de"), "abcde");
+        // A length running past the end is cut short.
+        assert_eq!(record_text(TYPE_TXT, b"\x09abc"), "abc");
+        assert_eq!(record_text(TYPE_A, &[192, 0, 2, 1]), "192.0.2.1");
+        assert_eq!(record_text(TYPE_A, &[192, 0, 2]), "");
+    }
+}
@@ -0,0 +1,252 @@
+use std::{cmp::Reverse, fmt::Display, sync::Mutex};
+
+use futures_util::future::join_all;
+
+use crate::Error;
+use crate::db::{LookupMode, LookupSource, LookupSources};
+use crate::dyndns::http_client::HttpClient;
+
+use super::{
+    AddressFamily, DnsIpLookup, HttpIpLookup, IpLookup, IpLookupParser, LookupAnswer, SourceParser,
+    StunIpLookup,
+};
+
+/// A single configured source, queried over HTTP, DNS or STUN.
+pub enum SourceLookup<'a, T> {
+    Http(HttpIpLookup<'a, SourceParser, T>),
+    Dns(DnsIpLookup<'a, SourceParser, T>),
+    Stun(StunIpLookup<'a, T>),
+}
+
+impl<'a, T> SourceLookup<'a, T> {
+    fn new(
+        client: &'a HttpClient,
+        interface: &'a str,
+        source: &'a LookupSource,
+        sources: &'a LookupSources,
+    ) -> Self {
+        if let Some(server) = source.stun_server() {
+            let peer = sources
+                .iter()
+                .filter_map(LookupSource::stun_server)
+                .find(|peer| *peer != server);
+            return Self::Stun(StunIpLookup::new(
+                interface,
+                source.url.as_str(),
+                server,
+                peer,
+            ));
+        }
+        let parser = SourceParser::from(source);
+        match source.dns_target() {
+            Ok(Some(target)) => Self::Dns(DnsIpLookup::new(
+                interface,
+                source.url.as_str(),
+                target,
+                parser,
+            )),
+            _ => Self::Http(HttpIpLookup::new(
+                client,
+                interface,
+                source.url.as_str(),
+                parser,
+            )),
+        }
+    }
+
+    pub fn source(&self) -> &'a str {
+        match self {
+            Self::Http(lookup) => lookup.url(),
+            Self::Dns(lookup) => lookup.url(),
+            Self::Stun(lookup) => lookup.url(),
+        }
+    }
+
+    fn take_warnings(&self) -> Vec<String> {
+        match self {
+            Self::Stun(lookup) => lookup.take_warnings(),
+            _ => vec![],
+        }
+    }
+}
+
+impl<'a, T> IpLookup<T> for SourceLookup<'a, T>
+where
+    T: AddressFamily + Send + Sync + 'a,
+    SourceParser: IpLookupParser<T>,
+{
+    async fn lookup(&self) -> Result<T, Error> {
+        match self {
+            Self::Http(lookup) => lookup.lookup().await,
+            Self::Dns(lookup) => lookup.lookup().await,
+            Self::Stun(lookup) => lookup.lookup().await,
+        }
+    }
+}
+
+/// External address lookup built from the configured sources and mode.
+pub enum ExternalIpLookup<'a, T> {
+    Fallback(IpLookupChain<'a, T>),
+    Consensus(IpLookupConsensus<'a, T>),
+}
+
+impl<'a, T> ExternalIpLookup<'a, T> {
+    pub fn new(
+        client: &'a HttpClient,
+        interface: &'a str,
+        sources: &'a LookupSources,
+        mode: LookupMode,
+        quorum: usize,
+    ) -> Self {
+        let lookups = sources
+            .iter()
+            .map(|source| SourceLookup::new(client, interface, source, sources))
+            .collect();
+        match mode {
+            LookupMode::Fallback => Self::Fallback(IpLookupChain { lookups }),
+            LookupMode::Consensus => Self::Consensus(IpLookupConsensus {
+                interface,
+                lookups,
+                quorum,
+                warnings: Mutex::new(vec![]),
+            }),
+        }
+    }
+
+    /// Drains the disagreements and NAT reports noticed since the last call.
+    pub fn take_warnings(&self) -> Vec<String> {
+        let (lookups, mut warnings) = match self {
+            Self::Fallback(lookup) => (&lookup.lookups, vec![]),
+            Self::Consensus(lookup) => (
+                &lookup.lookups,
+                std::mem::take(&mut *lookup.warnings.lock().unwrap()),
+            ),
+        };
+        warnings.extend(lookups.iter().flat_map(SourceLookup::take_warnings));
+        warnings
+    }
+}
+
+impl<'a, T> IpLookup<LookupAnswer<T>> for ExternalIpLookup<'a, T>
+where
+    T: AddressFamily + Copy + PartialEq + Display + Send + Sync + 'a,
+    SourceParser: IpLookupParser<T>,
+{
+    async fn lookup(&self) -> Result<LookupAnswer<T>, Error> {
+        match self {
+            Self::Fallback(lookup) => lookup.lookup().await,
+            Self::Consensus(lookup) => lookup.lookup().await,
+        }
+    }
+}
+
+/// Queries the configured sources in order and returns the first answer.
+pub struct IpLookupChain<'a, T> {
+    lookups: Vec<SourceLookup<'a, T>>,
+}
+
+impl<'a, T> IpLookup<LookupAnswer<T>> for IpLookupChain<'a, T>
+where
+    T: AddressFamily + Send + Sync + 'a,
+    SourceParser: IpLookupParser<T>,
+{
+    async fn lookup(&self) -> Result<LookupAnswer<T>, Error> {
+        let mut last_error = None;
+        for lookup in &self.lookups {
+            match lookup.lookup().await {
+                Ok(address) => {
+                    return Ok(LookupAnswer {
+                        address,
+                        source: lookup.source().to_owned(),
+                    });
+                }
+                Err(err) => {
+                    warn!("ip lookup via {} failed: {}", lookup.source(), err);
+                    last_error = Some(err);
+                }
+            }
+        }
+        Err(last_error
+            .unwrap_or_else(|| Error::validation_failed("no ip lookup source configured")))
+    }
+}
+
+/// Queries every source concurrently and accepts an address only when
+/// `quorum` of them report it.
+pub struct IpLookupConsensus<'a, T> {
+    interface: &'a str,
+    lookups: Vec<SourceLookup<'a, T>>,
+    quorum: usize,
+    warnings: Mutex<Vec<String>>,
+}
+
+impl<'a, T> IpLookupConsensus<'a, T> {
+    fn warn(&self, message: String) {
+        warn!("{}", message);
+        self.warnings.lock().unwrap().push(message);
+    }
+}
+
+impl<'a, T> IpLookup<LookupAnswer<T>> for IpLookupConsensus<'a, T>
+where
+    T: AddressFamily + Copy + PartialEq + Display + Send + Sync + 'a,
+    SourceParser: IpLookupParser<T>,
+{
+    async fn lookup(&self) -> Result<LookupAnswer<T>, Error> {
+        let answers = join_all(
+            self.lookups
+                .iter()
+                .map(|lookup| async move { (lookup.source(), lookup.lookup().await) }),
+        )
+        .await;
+
+        let mut tally: Vec<(T, Vec<&str>)> = vec![];
+        for (url, answer) in answers {
+            match answer {
+                Ok(address) => match tally.iter_mut().find(|(known, _)| *known == address) {
+                    Some((_, sources)) => sources.push(url),
+                    None => tally.push((address, vec![url])),
+                },
+                Err(err) => warn!("ip lookup via {} failed: {}", url, err),
+            }
+        }
+        tally.sort_by_key(|(_, sources)| Reverse(sources.len()));
+
+        let summary = tally
+            .iter()
+            .map(|(address, sources)| format!("{} from {}", address, sources.join(", ")))
+            .collect::<Vec<_>>()
+            .join("; ");
+        let votes = tally.first().map_or(0, |(_, sources)| sources.len());
+        let tied = tally
+            .get(1)
+            .is_some_and(|(_, sources)| sources.len() == votes);
+
+        if votes < self.quorum || tied {
+            let message = format!(
+                "ip lookup on {} did not reach a quorum of {}: {}",
+                self.interface,
+                self.quorum,
+                if summary.is_empty() {
+                    "no source answered"
+                } else {
+                    &summary
+                }
+            );
+            self.warn(message.clone());
+            return Err(Error::no_quorum(message));
+        }
+
+        let (address, sources) = &tally[0];
+        if tally.len() > 1 {
+            self.warn(format!(
+                "ip lookup sources on {} disagree, accepted {}: {}",
+                self.interface, address, summary
+            ));
+        }
+        Ok(LookupAnswer {
+            address: *address,
+            source: format!("consensus of {}", sources.join(", ")),
+        })
+    }
+}
@@ -1,7 +1,4 @@
-use std::{
-    marker::PhantomData,
-    net::{Ipv4Addr, Ipv6Addr},
-};
+use std::marker::PhantomData;
 
 use isahc::{
     Request,
@@ -12,18 +9,18 @@ use isahc::{
 use crate::Error;
 use crate::dyndns::http_client::HttpClient;
 
-use super::{IpLookup, IpLookupParser, PlainTextIpParser};
+use super::{IpLookup, IpLookupParser};
 
 pub struct HttpIpLookup<'a, P, T> {
     client: &'a HttpClient,
     interface: &'a str,
-    url: &'static str,
+    url: &'a str,
     parser: P,
     _marker: PhantomData<T>,
 }
 
 impl<'a, P, T> HttpIpLookup<'a, P, T> {
-    pub fn new(client: &'a HttpClient, interface: &'a str, url: &'static str, parser: P) -> Self {
+    pub fn new(client: &'a HttpClient, interface: &'a str, url: &'a str, parser: P) -> Self {
         Self {
             client,
             interface,
@@ -32,6 +29,10 @@ impl<'a, P, T> HttpIpLookup<'a, P, T> {
             _marker: PhantomData,
         }
     }
+
+    pub fn url(&self) -> &'a str {
+        self.url
+    }
 }
 
 impl<'a, T, P> IpLookup<T> for HttpIpLookup<'a, P, T>
@@ -50,27 +51,3 @@ where
         self.parser.parse(&body)
     }
 }
-
-pub type IpsbLookup<'a, T> = HttpIpLookup<'a, PlainTextIpParser, T>;
-
-impl<'a> HttpIpLookup<'a, PlainTextIpParser, Ipv4Addr> {
-    pub fn ipsb(client: &'a HttpClient, interface: &'a str) -> Self {
-        Self::new(
-            client,
-            interface,
-            "https://api-ipv4.ip.sb/ip",
-            PlainTextIpParser,
-        )
-    }
-}
-
-impl<'a> HttpIpLookup<'a, PlainTextIpParser, Ipv6Addr> {
-    pub fn ipsb(client: &'a HttpClient, interface: &'a str) -> Self {
-        Self::new(
-            client,
-            interface,
-            "https://api-ipv6.ip.sb/ip",
-            PlainTextIpParser,
-        )
-    }
-}
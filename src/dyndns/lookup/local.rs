@@ -2,32 +2,58 @@ use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 use local_ip_address::list_afinet_netifas;
 
-use crate::Error;
+use crate::{Error, db::Ipv6Selection};
 
-use super::IpLookup;
+use super::{
+    IpLookup,
+    netlink::{Ipv6AddressInfo, ipv6_addresses},
+};
 
 pub struct LocalIpv6Lookup<'a> {
     interface: &'a str,
+    selection: Ipv6Selection,
 }
 
 impl<'a> LocalIpv6Lookup<'a> {
-    pub fn new(interface: &'a str) -> Self {
-        Self { interface }
+    pub fn new(interface: &'a str, selection: Ipv6Selection) -> Self {
+        Self {
+            interface,
+            selection,
+        }
     }
-}
 
-impl<'a> IpLookup<Vec<Ipv6Addr>> for LocalIpv6Lookup<'a> {
-    async fn lookup(&self) -> Result<Vec<Ipv6Addr>, Error> {
-        let ifaces = list_afinet_netifas()?;
-        let mut ipv6_addresses = vec![];
-        for (name, ip) in ifaces {
-            if let IpAddr::V6(addr) = ip {
-                if name == self.interface && (addr.segments()[0] & 0xffc0) != 0xfe80 {
-                    ipv6_addresses.push(addr);
+    fn select(&self, mut candidates: Vec<Ipv6AddressInfo>) -> Vec<Ipv6Addr> {
+        candidates.retain(|info| !info.is_link_local() && !info.is_tentative());
+        match self.selection {
+            Ipv6Selection::All => {}
+            Ipv6Selection::Stable => {
+                candidates.retain(|info| {
+                    !info.is_temporary()
+                        && !info.is_deprecated()
+                        && !info.is_unique_local()
+                        && !info.address.is_loopback()
+                });
+                if candidates.iter().any(Ipv6AddressInfo::is_eui64) {
+                    candidates.retain(Ipv6AddressInfo::is_eui64);
                 }
             }
+            Ipv6Selection::Suffix { suffix } => {
+                let interface_id = |address: Ipv6Addr| u128::from(address) & u64::MAX as u128;
+                candidates.retain(|info| {
+                    !info.is_deprecated()
+                        && !info.is_unique_local()
+                        && interface_id(info.address) == interface_id(suffix)
+                });
+            }
         }
+        candidates.sort_by_key(|info| std::cmp::Reverse(info.preferred_lifetime));
+        candidates.into_iter().map(|info| info.address).collect()
+    }
+}
 
+impl<'a> IpLookup<Vec<Ipv6Addr>> for LocalIpv6Lookup<'a> {
+    async fn lookup(&self) -> Result<Vec<Ipv6Addr>, Error> {
+        let ipv6_addresses = self.select(ipv6_addresses(self.interface)?);
         if ipv6_addresses.is_empty() {
             Err(Error::ipv6_not_found())
         } else {
@@ -36,32 +62,112 @@ impl<'a> IpLookup<Vec<Ipv6Addr>> for LocalIpv6Lookup<'a> {
     }
 }
 
-#[allow(dead_code)]
 pub struct LocalIpv4Lookup<'a> {
     interface: &'a str,
 }
 
-#[allow(dead_code)]
 impl<'a> LocalIpv4Lookup<'a> {
     pub fn new(interface: &'a str) -> Self {
         Self { interface }
     }
+
+    /// Every IPv4 address on the interface except loopback and link-local ones.
+    pub fn addresses(&self) -> Result<Vec<Ipv4Addr>, Error> {
+        let ifaces = list_afinet_netifas()?;
+        Ok(ifaces
+            .into_iter()
+            .filter_map(|(name, ip)| match ip {
+                IpAddr::V4(addr) if name == self.interface => Some(addr),
+                _ => None,
+            })
+            .filter(|addr| !addr.is_loopback() && !addr.is_link_local())
+            .collect())
+    }
 }
 
 impl<'a> IpLookup<Ipv4Addr> for LocalIpv4Lookup<'a> {
     async fn lookup(&self) -> Result<Ipv4Addr, Error> {
-        let ifaces = list_afinet_netifas()?;
-        for (name, ip) in ifaces {
-            if name == self.interface {
-                if let IpAddr::V4(addr) = ip {
-                    if addr.is_private() || addr.is_loopback() || addr.is_link_local() {
-                        continue;
-                    }
-
-                    return Ok(addr);
-                }
-            }
-        }
-        Err(Error::ipv4_not_found())
+        self.addresses()?
+            .into_iter()
+            .find(|addr| !addr.is_private() && !is_shared(addr))
+            .ok_or_else(Error::ipv4_not_found)
+    }
+}
+
+/// Whether the address is in the 100.64.0.0/10 carrier-grade NAT range.
+pub fn is_shared(addr: &Ipv4Addr) -> bool {
+    let [a, b, ..] = addr.octets();
+    a == 100 && (b & 0xc0) == 64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::netlink::{
+        IFA_F_DADFAILED, IFA_F_DEPRECATED, IFA_F_TEMPORARY, IFA_F_TENTATIVE,
+    };
+    use super::*;
+
+    fn candidates() -> Vec<Ipv6AddressInfo> {
+        vec![
+            Ipv6AddressInfo::new("fe80::211:22ff:fe33:4455", 0, u32::MAX),
+            Ipv6AddressInfo::new("2001:db8::8a2e:370:7334", IFA_F_TEMPORARY, 86_400),
+            Ipv6AddressInfo::new("2001:db8::1234:5678", 0, 3_600),
+            Ipv6AddressInfo::new("2001:db8::211:22ff:fe33:4455", 0, 14_400),
+            Ipv6AddressInfo::new("2001:db8:0:1::1234:5678", IFA_F_DEPRECATED, 0),
+            Ipv6AddressInfo::new("2001:db8:0:2::1", IFA_F_TENTATIVE, u32::MAX),
+            Ipv6AddressInfo::new("2001:db8:0:3::1", IFA_F_DADFAILED, u32::MAX),
+            Ipv6AddressInfo::new("fd00::1234:5678", 0, u32::MAX),
+        ]
+    }
+
+    fn select(selection: Ipv6Selection, candidates: Vec<Ipv6AddressInfo>) -> Vec<String> {
+        LocalIpv6Lookup::new("lo", selection)
+            .select(candidates)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn stable_prefers_eui64() {
+        assert_eq!(
+            select(Ipv6Selection::Stable, candidates()),
+            ["2001:db8::211:22ff:fe33:4455"]
+        );
+    }
+
+    #[test]
+    fn stable_without_eui64_skips_temporary_and_ula() {
+        let mut candidates = candidates();
+        candidates.retain(|info| !info.is_eui64());
+        candidates.push(Ipv6AddressInfo::new("::1", 0, u32::MAX));
+        assert_eq!(
+            select(Ipv6Selection::Stable, candidates),
+            ["2001:db8::1234:5678"]
+        );
+    }
+
+    #[test]
+    fn suffix_matches_interface_identifier() {
+        let suffix = "::1234:5678".parse().unwrap();
+        // The deprecated and the ULA address share the suffix.
+        assert_eq!(
+            select(Ipv6Selection::Suffix { suffix }, candidates()),
+            ["2001:db8::1234:5678"]
+        );
+    }
+
+    #[test]
+    fn all_orders_by_preferred_lifetime() {
+        assert_eq!(
+            select(Ipv6Selection::All, candidates()),
+            [
+                "fd00::1234:5678",
+                "2001:db8::8a2e:370:7334",
+                "2001:db8::211:22ff:fe33:4455",
+                "2001:db8::1234:5678",
+                "2001:db8:0:1::1234:5678",
+            ]
+        );
     }
 }
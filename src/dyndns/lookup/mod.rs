@@ -1,14 +1,65 @@
+mod dns;
+mod external;
 mod http;
 mod local;
+mod netlink;
 mod parser;
+mod stun;
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 use crate::Error;
 
-pub use http::{HttpIpLookup, IpsbLookup};
-#[allow(unused_imports)]
-pub use local::{LocalIpv4Lookup, LocalIpv6Lookup};
-pub use parser::{IpLookupParser, PlainTextIpParser};
+use super::dns::message::{TYPE_A, TYPE_AAAA};
+
+pub use dns::DnsIpLookup;
+pub use external::ExternalIpLookup;
+pub use http::HttpIpLookup;
+pub use local::{LocalIpv4Lookup, LocalIpv6Lookup, is_shared};
+pub use netlink::{interface_index, watch_addresses};
+pub use parser::{IpLookupParser, SourceParser};
+pub use stun::StunIpLookup;
 
 pub trait IpLookup<T>: Send + Sync {
     async fn lookup(&self) -> Result<T, Error>;
 }
+
+/// An address together with the source that reported it.
+#[derive(Debug, Clone)]
+pub struct LookupAnswer<T> {
+    pub address: T,
+    pub source: String,
+}
+
+/// Per-family details needed by lookups that talk to servers directly.
+pub trait AddressFamily: Sized {
+    /// Record type queried when a DNS source does not name one.
+    const RECORD_TYPE: u16;
+    const UNSPECIFIED: IpAddr;
+
+    fn from_ip(ip: IpAddr) -> Option<Self>;
+}
+
+impl AddressFamily for Ipv4Addr {
+    const RECORD_TYPE: u16 = TYPE_A;
+    const UNSPECIFIED: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+
+    fn from_ip(ip: IpAddr) -> Option<Self> {
+        match ip {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        }
+    }
+}
+
+impl AddressFamily for Ipv6Addr {
+    const RECORD_TYPE: u16 = TYPE_AAAA;
+    const UNSPECIFIED: IpAddr = IpAddr::V6(Ipv6Addr::UNSPECIFIED);
+
+    fn from_ip(ip: IpAddr) -> Option<Self> {
+        match ip {
+            IpAddr::V4(_) => None,
+            IpAddr::V6(ip) => Some(ip),
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    thread,
+    time::Duration,
+};
+
+use neli::{
+    consts::{
+        nl::{NlmF, NlmFFlags},
+        rtnl::{Ifa, IfaF, IfaFFlags, RtAddrFamily, Rtm},
+        socket::NlFamily,
+    },
+    nl::{NlPayload, Nlmsghdr},
+    rtnl::Ifaddrmsg,
+    socket::NlSocketHandle,
+    types::RtBuffer,
+};
+
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::Error;
+
+const RTNLGRP_IPV4_IFADDR: u32 = 5;
+const RTNLGRP_IPV6_IFADDR: u32 = 9;
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+pub(super) const IFA_F_TEMPORARY: u32 = 0x01;
+pub(super) const IFA_F_DADFAILED: u32 = 0x08;
+pub(super) const IFA_F_DEPRECATED: u32 = 0x20;
+pub(super) const IFA_F_TENTATIVE: u32 = 0x40;
+
+/// An IPv6 address on an interface with the kernel's flags and lifetimes.
+#[derive(Debug, Clone, Copy)]
+pub struct Ipv6AddressInfo {
+    pub address: Ipv6Addr,
+    flags: u32,
+    /// Seconds the address stays preferred, `u32::MAX` for forever.
+    pub preferred_lifetime: u32,
+}
+
+impl Ipv6AddressInfo {
+    #[cfg(test)]
+    pub(super) fn new(address: &str, flags: u32, preferred_lifetime: u32) -> Self {
+        Self {
+            address: address.parse().unwrap(),
+            flags,
+            preferred_lifetime,
+        }
+    }
+
+    pub fn is_temporary(&self) -> bool {
+        self.flags & IFA_F_TEMPORARY != 0
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        self.flags & IFA_F_DEPRECATED != 0 || self.preferred_lifetime == 0
+    }
+
+    /// Still running or failed duplicate address detection.
+    pub fn is_tentative(&self) -> bool {
+        self.flags & (IFA_F_TENTATIVE | IFA_F_DADFAILED) != 0
+    }
+
+    pub fn is_link_local(&self) -> bool {
+        (self.address.segments()[0] & 0xffc0) == 0xfe80
+    }
+
+    pub fn is_unique_local(&self) -> bool {
+        (self.address.segments()[0] & 0xfe00) == 0xfc00
+    }
+
+    /// Interface identifier derived from a MAC address (`xx:xxff:fexx:xxxx`).
+    pub fn is_eui64(&self) -> bool {
+        let octets = self.address.octets();
+        octets[11] == 0xff && octets[12] == 0xfe
+    }
+}
+
+/// Dumps the IPv6 addresses of `interface` over rtnetlink.
+pub fn ipv6_addresses(interface: &str) -> Result<Vec<Ipv6AddressInfo>, Error> {
+    let index = interface_index(interface)?;
+    let mut socket = NlSocketHandle::connect(NlFamily::Route, None, &[]).map_err(netlink_error)?;
+
+    let request = Ifaddrmsg {
+        ifa_family: RtAddrFamily::Inet6,
+        ifa_prefixlen: 0,
+        ifa_flags: IfaFFlags::empty(),
+        ifa_scope: 0,
+        ifa_index: 0,
+        rtattrs: RtBuffer::new(),
+    };
+    socket
+        .send(Nlmsghdr::new(
+            None,
+            Rtm::Getaddr,
+            NlmFFlags::new(&[NlmF::Request, NlmF::Dump]),
+            None,
+            None,
+            NlPayload::Payload(request),
+        ))
+        .map_err(netlink_error)?;
+
+    let mut addresses = vec![];
+    for response in socket.iter::<Rtm, Ifaddrmsg>(false) {
+        let header = response.map_err(netlink_error)?;
+        let NlPayload::Payload(message) = header.nl_payload else {
+            continue;
+        };
+        if message.ifa_index != index {
+            continue;
+        }
+        if let Some(RawAddress {
+            address: IpAddr::V6(address),
+            flags,
+            preferred_lifetime,
+        }) = RawAddress::parse(&message)
+        {
+            addresses.push(Ipv6AddressInfo {
+                address,
+                flags,
+                preferred_lifetime,
+            });
+        }
+    }
+    Ok(addresses)
+}
+
+/// Subscribes to rtnetlink address notifications and reports the index of every
+/// interface on which an address appears, disappears or changes its flags.
+///
+/// Lifetime refreshes from router advertisements are not reported. The
+/// subscription runs on its own thread until the receiver is dropped.
+pub fn watch_addresses() -> Result<mpsc::Receiver<i32>, Error> {
+    let mut socket = subscribe()?;
+    let (tx, rx) = mpsc::channel(16);
+    thread::Builder::new()
+        .name("netlink-watch".to_owned())
+        .spawn(move || {
+            let mut known: HashMap<(i32, IpAddr), u32> = HashMap::new();
+            loop {
+                let (kind, message) = match socket.recv::<Rtm, Ifaddrmsg>() {
+                    Ok(Some(header)) => match header.nl_payload {
+                        NlPayload::Payload(message) => (header.nl_type, message),
+                        _ => continue,
+                    },
+                    Ok(None) => continue,
+                    Err(err) => {
+                        // A message that fails to parse stays at the head of the
+                        // buffer, so start over on a fresh socket.
+                        warn!("netlink address watch failed: {}", err);
+                        thread::sleep(RECONNECT_DELAY);
+                        match subscribe() {
+                            Ok(fresh) => socket = fresh,
+                            Err(err) => error!("{}", err),
+                        }
+                        continue;
+                    }
+                };
+                let Some(raw) = RawAddress::parse(&message) else {
+                    continue;
+                };
+
+                let key = (message.ifa_index, raw.address);
+                let changed = match kind {
+                    Rtm::Newaddr => known.insert(key, raw.flags) != Some(raw.flags),
+                    Rtm::Deladdr => {
+                        known.remove(&key);
+                        true
+                    }
+                    _ => false,
+                };
+                if !changed {
+                    continue;
+                }
+
+                debug!(
+                    "netlink {:?} {} on interface {}",
+                    kind, raw.address, message.ifa_index
+                );
+                match tx.try_send(message.ifa_index) {
+                    Ok(()) | Err(TrySendError::Full(_)) => {}
+                    Err(TrySendError::Closed(_)) => return,
+                }
+            }
+        })
+        .map_err(netlink_error)?;
+    Ok(rx)
+}
+
+fn subscribe() -> Result<NlSocketHandle, Error> {
+    NlSocketHandle::connect(
+        NlFamily::Route,
+        None,
+        &[RTNLGRP_IPV4_IFADDR, RTNLGRP_IPV6_IFADDR],
+    )
+    .map_err(netlink_error)
+}
+
+/// Address, flags and preferred lifetime carried by an `ifaddrmsg`.
+struct RawAddress {
+    address: IpAddr,
+    flags: u32,
+    preferred_lifetime: u32,
+}
+
+impl RawAddress {
+    fn parse(message: &Ifaddrmsg) -> Option<Self> {
+        let mut address = None;
+        let mut flags = [
+            IfaF::Temporary,
+            IfaF::Dadfailed,
+            IfaF::Deprecated,
+            IfaF::Tentative,
+        ]
+        .into_iter()
+        .filter(|flag| message.ifa_flags.contains(flag))
+        .fold(0u32, |flags, flag| flags | u8::from(flag) as u32);
+        let mut preferred_lifetime = u32::MAX;
+
+        for attr in message.rtattrs.iter() {
+            let payload = attr.rta_payload.as_ref();
+            match attr.rta_type {
+                Ifa::Address => match message.ifa_family {
+                    RtAddrFamily::Inet6 => {
+                        if let Ok(octets) = <[u8; 16]>::try_from(payload) {
+                            address = Some(IpAddr::V6(Ipv6Addr::from(octets)));
+                        }
+                    }
+                    RtAddrFamily::Inet => {
+                        if let Ok(octets) = <[u8; 4]>::try_from(payload) {
+                            address = Some(IpAddr::V4(Ipv4Addr::from(octets)));
+                        }
+                    }
+                    _ => {}
+                },
+                Ifa::Flags => {
+                    if let Ok(bytes) = <[u8; 4]>::try_from(payload) {
+                        flags = u32::from_ne_bytes(bytes);
+                    }
+                }
+                Ifa::Cacheinfo => {
+                    if let Some(bytes) = payload.get(..4).and_then(|b| <[u8; 4]>::try_from(b).ok())
+                    {
+                        preferred_lifetime = u32::from_ne_bytes(bytes);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            address: address?,
+            flags,
+            preferred_lifetime,
+        })
+    }
+}
+
+pub fn interface_index(interface: &str) -> Result<i32, Error> {
+    let path = format!("/sys/class/net/{interface}/ifindex");
+    std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|index| index.trim().parse().ok())
+        .ok_or_else(|| Error::netlink(format!("interface {interface} not found")))
+}
+
+fn netlink_error(err: impl std::fmt::Display) -> Error {
+    Error::netlink(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_address_ranges() {
+        let info = |address| Ipv6AddressInfo::new(address, 0, u32::MAX);
+        assert!(info("fe80::1").is_link_local());
+        assert!(info("febf::1").is_link_local());
+        assert!(!info("fec0::1").is_link_local());
+        assert!(info("fc00::1").is_unique_local());
+        assert!(info("fdab:cdef::1").is_unique_local());
+        assert!(!info("fe00::1").is_unique_local());
+        assert!(info("2001:db8::211:22ff:fe33:4455").is_eui64());
+        assert!(!info("2001:db8::1").is_eui64());
+    }
+
+    #[test]
+    fn reads_flags_and_lifetime() {
+        let info = Ipv6AddressInfo::new("2001:db8::1", IFA_F_TEMPORARY, 3600);
+        assert!(info.is_temporary());
+        assert!(!info.is_deprecated());
+        assert!(Ipv6AddressInfo::new("2001:db8::1", 0, 0).is_deprecated());
+        assert!(Ipv6AddressInfo::new("2001:db8::1", IFA_F_DEPRECATED, 3600).is_deprecated());
+        assert!(Ipv6AddressInfo::new("2001:db8::1", IFA_F_TENTATIVE, 3600).is_tentative());
+        assert!(Ipv6AddressInfo::new("2001:db8::1", IFA_F_DADFAILED, 3600).is_tentative());
+    }
+}
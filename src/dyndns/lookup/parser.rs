@@ -1,6 +1,14 @@
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::{
+    net::{Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
 
-use crate::Error;
+use regex::Regex;
+
+use crate::{
+    Error,
+    db::{LookupParserKind, LookupSource},
+};
 
 pub trait IpLookupParser<T>: Send + Sync {
     fn parse(&self, body: &str) -> Result<T, Error>;
@@ -26,3 +34,219 @@ impl IpLookupParser<Ipv6Addr> for PlainTextIpParser {
             .map_err(|_err| Error::ipv6_parse_error(trimmed))
     }
 }
+
+/// Extracts the address from a JSON body at the given pointer, e.g. `/ip`.
+#[derive(Clone)]
+pub struct JsonPointerParser {
+    pointer: String,
+}
+
+impl JsonPointerParser {
+    pub fn new(pointer: impl Into<String>) -> Self {
+        Self {
+            pointer: pointer.into(),
+        }
+    }
+
+    fn extract<T: FromStr>(&self, body: &str, err: fn(String) -> Error) -> Result<T, Error> {
+        let value: serde_json::Value =
+            serde_json::from_str(body).map_err(|_err| err(body.trim().to_owned()))?;
+        let found = value
+            .pointer(&self.pointer)
+            .and_then(|value| value.as_str())
+            .ok_or_else(|| err(format!("{} not found in {}", self.pointer, body.trim())))?;
+        found.trim().parse().map_err(|_err| err(found.to_owned()))
+    }
+}
+
+impl IpLookupParser<Ipv4Addr> for JsonPointerParser {
+    fn parse(&self, body: &str) -> Result<Ipv4Addr, Error> {
+        self.extract(body, Error::ipv4_parse_error)
+    }
+}
+
+impl IpLookupParser<Ipv6Addr> for JsonPointerParser {
+    fn parse(&self, body: &str) -> Result<Ipv6Addr, Error> {
+        self.extract(body, Error::ipv6_parse_error)
+    }
+}
+
+/// Extracts the address with a regular expression, using the `ip` named group,
+/// else the first capture group, else the whole match.
+#[derive(Clone)]
+pub struct RegexParser {
+    regex: Regex,
+}
+
+impl RegexParser {
+    pub fn new(regex: Regex) -> Self {
+        Self { regex }
+    }
+
+    fn extract<T: FromStr>(&self, body: &str, err: fn(String) -> Error) -> Result<T, Error> {
+        let captures = self
+            .regex
+            .captures(body)
+            .ok_or_else(|| err(format!("{} did not match {}", self.regex, body.trim())))?;
+        let found = captures
+            .name("ip")
+            .or_else(|| captures.get(1))
+            .or_else(|| captures.get(0))
+            .map(|found| found.as_str().trim())
+            .unwrap_or_default();
+        found.parse().map_err(|_err| err(found.to_owned()))
+    }
+}
+
+impl IpLookupParser<Ipv4Addr> for RegexParser {
+    fn parse(&self, body: &str) -> Result<Ipv4Addr, Error> {
+        self.extract(body, Error::ipv4_parse_error)
+    }
+}
+
+impl IpLookupParser<Ipv6Addr> for RegexParser {
+    fn parse(&self, body: &str) -> Result<Ipv6Addr, Error> {
+        self.extract(body, Error::ipv6_parse_error)
+    }
+}
+
+/// Parser selected by a configured lookup source.
+pub enum SourceParser {
+    PlainText(PlainTextIpParser),
+    JsonPointer(JsonPointerParser),
+    Regex(RegexParser),
+}
+
+impl From<&LookupSource> for SourceParser {
+    fn from(source: &LookupSource) -> Self {
+        let expression = source.expression.as_deref().unwrap_or_default();
+        match source.parser {
+            LookupParserKind::PlainText => Self::PlainText(PlainTextIpParser),
+            LookupParserKind::JsonPointer => Self::JsonPointer(JsonPointerParser::new(expression)),
+            LookupParserKind::Regex => Self::Regex(RegexParser::new(
+                Regex::new(expression).expect("lookup regex is validated on save"),
+            )),
+        }
+    }
+}
+
+impl<T> IpLookupParser<T> for SourceParser
+where
+    PlainTextIpParser: IpLookupParser<T>,
+    JsonPointerParser: IpLookupParser<T>,
+    RegexParser: IpLookupParser<T>,
+{
+    fn parse(&self, body: &str) -> Result<T, Error> {
+        match self {
+            Self::PlainText(parser) => parser.parse(body),
+            Self::JsonPointer(parser) => parser.parse(body),
+            Self::Regex(parser) => parser.parse(body),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(parser: LookupParserKind, expression: &str) -> SourceParser {
+        SourceParser::from(&LookupSource {
+            url: "https://example.com/ip".to_owned(),
+            parser,
+            expression: Some(expression.to_owned()),
+        })
+    }
+
+    #[test]
+    fn parses_plain_text() {
+        let v4: Ipv4Addr = PlainTextIpParser.parse(" 203.0.113.7\n").unwrap();
+        assert_eq!(v4, Ipv4Addr::new(203, 0, 113, 7));
+        let v6: Ipv6Addr = PlainTextIpParser.parse("2001:db8::1\n").unwrap();
+        assert_eq!(v6, "2001:db8::1".parse::<Ipv6Addr>().unwrap());
+        assert!(IpLookupParser::<Ipv4Addr>::parse(&PlainTextIpParser, "2001:db8::1").is_err());
+    }
+
+    #[test]
+    fn extracts_json_pointer() {
+        let parser = JsonPointerParser::new("/data/ip");
+        let body = r#"{"data": {"ip": " 203.0.113.7 ", "country": "NL"}}"#;
+        let address: Ipv4Addr = parser.parse(body).unwrap();
+        assert_eq!(address, Ipv4Addr::new(203, 0, 113, 7));
+
+        let parser = JsonPointerParser::new("/addresses/1");
+        let address: Ipv6Addr = parser
+            .parse(r#"{"addresses": ["203.0.113.7", "2001:db8::1"]}"#)
+            .unwrap();
+        assert_eq!(address, "2001:db8::1".parse::<Ipv6Addr>().unwrap());
+    }
+
+    #[test]
+    fn reports_json_pointer_failures() {
+        let parser = JsonPointerParser::new("/ip");
+        let parse = |body| IpLookupParser::<Ipv4Addr>::parse(&parser, body).unwrap_err();
+
+        assert_eq!(
+            parse(r#"{"addr": "203.0.113.7"}"#).to_string(),
+            r#"Failed to parse IPv4 address : /ip not found in {"addr": "203.0.113.7"}"#
+        );
+        // Only strings are taken.
+        assert!(
+            parse(r#"{"ip": 3405803783}"#)
+                .to_string()
+                .contains("not found")
+        );
+        assert!(
+            parse(r#"{"ip": "unknown"}"#)
+                .to_string()
+                .ends_with(": unknown")
+        );
+        assert!(parse("203.0.113.7 <html>").to_string().ends_with("<html>"));
+    }
+
+    #[test]
+    fn prefers_named_group_then_first_group_then_match() {
+        let body = "Current IP Address: 203.0.113.7 (via 198.51.100.1)";
+
+        let named = RegexParser::new(Regex::new(r"via (?P<ip>[\d.]+)").unwrap());
+        let address: Ipv4Addr = named.parse(body).unwrap();
+        assert_eq!(address, Ipv4Addr::new(198, 51, 100, 1));
+
+        let group = RegexParser::new(Regex::new(r"Address: ([\d.]+)").unwrap());
+        let address: Ipv4Addr = group.parse(body).unwrap();
+        assert_eq!(address, Ipv4Addr::new(203, 0, 113, 7));
+
+        let whole = RegexParser::new(Regex::new(r"[0-9a-f]*:[0-9a-f:]+").unwrap());
+        let address: Ipv6Addr = whole.parse("ip=2001:db8::1;").unwrap();
+        assert_eq!(address, "2001:db8::1".parse::<Ipv6Addr>().unwrap());
+    }
+
+    #[test]
+    fn reports_regex_failures() {
+        let parser = RegexParser::new(Regex::new(r"ip=(\S+)").unwrap());
+        let err = IpLookupParser::<Ipv4Addr>::parse(&parser, "no address").unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with(r"ip=(\S+) did not match no address")
+        );
+        let err = IpLookupParser::<Ipv4Addr>::parse(&parser, "ip=localhost").unwrap_err();
+        assert!(err.to_string().ends_with(": localhost"));
+    }
+
+    #[test]
+    fn selects_parser_of_source() {
+        let address: Ipv4Addr = source(LookupParserKind::JsonPointer, "/ip")
+            .parse(r#"{"ip": "203.0.113.7"}"#)
+            .unwrap();
+        assert_eq!(address, Ipv4Addr::new(203, 0, 113, 7));
+
+        let address: Ipv4Addr = source(LookupParserKind::Regex, r"ip=(\S+)")
+            .parse("ip=203.0.113.7")
+            .unwrap();
+        assert_eq!(address, Ipv4Addr::new(203, 0, 113, 7));
+
+        let address: Ipv4Addr = source(LookupParserKind::PlainText, "ignored")
+            .parse("203.0.113.7")
+            .unwrap();
+        assert_eq!(address, Ipv4Addr::new(203, 0, 113, 7));
+    }
+}
@@ -0,0 +1,347 @@
+use std::{
+    marker::PhantomData,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Mutex,
+    time::Duration,
+};
+
+use rand_core::{OsRng, RngCore};
+use tokio::{
+    net::{UdpSocket, lookup_host},
+    time::timeout,
+};
+
+use crate::Error;
+
+use super::{AddressFamily, IpLookup};
+
+const STUN_PORT: u16 = 3478;
+const MAGIC_COOKIE: u32 = 0x2112_a442;
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_RESPONSE: u16 = 0x0101;
+const ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+const HEADER_LEN: usize = 20;
+const ATTEMPTS: usize = 3;
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Sends an RFC 5389 binding request and reports the XOR-MAPPED-ADDRESS.
+///
+/// When another STUN server is configured it is asked from the same socket;
+/// a different mapping means the NAT is symmetric and is reported as a warning.
+pub struct StunIpLookup<'a, T> {
+    interface: &'a str,
+    url: &'a str,
+    server: &'a str,
+    peer: Option<&'a str>,
+    warnings: Mutex<Vec<String>>,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T> StunIpLookup<'a, T> {
+    pub fn new(interface: &'a str, url: &'a str, server: &'a str, peer: Option<&'a str>) -> Self {
+        Self {
+            interface,
+            url,
+            server,
+            peer,
+            warnings: Mutex::new(vec![]),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn url(&self) -> &'a str {
+        self.url
+    }
+
+    /// Drains the symmetric NAT reports noticed since the last call.
+    pub fn take_warnings(&self) -> Vec<String> {
+        std::mem::take(&mut *self.warnings.lock().unwrap())
+    }
+}
+
+impl<'a, T> IpLookup<T> for StunIpLookup<'a, T>
+where
+    T: AddressFamily + Send + Sync + 'a,
+{
+    async fn lookup(&self) -> Result<T, Error> {
+        let socket = UdpSocket::bind((T::UNSPECIFIED, 0)).await?;
+        socket.bind_device(Some(self.interface.as_bytes()))?;
+
+        let server = resolve::<T>(self.server).await?;
+        let mapped = binding(&socket, server).await?;
+
+        if let Some(peer) = self.peer {
+            match resolve::<T>(peer).await {
+                Ok(peer_addr) => match binding(&socket, peer_addr).await {
+                    Ok(peer_mapped) if peer_mapped != mapped => {
+                        let message = format!(
+                            "symmetric NAT detected on {}: {} mapped {}, {} mapped {}",
+                            self.interface, self.server, mapped, peer, peer_mapped
+                        );
+                        warn!("{}", message);
+                        self.warnings.lock().unwrap().push(message);
+                    }
+                    Ok(_) => {}
+                    Err(err) => debug!("stun binding via {} failed: {}", peer, err),
+                },
+                Err(err) => debug!("stun binding via {} failed: {}", peer, err),
+            }
+        }
+
+        T::from_ip(mapped.ip()).ok_or_else(|| {
+            Error::stun(format!(
+                "{} mapped an address of the wrong family: {}",
+                self.server, mapped
+            ))
+        })
+    }
+}
+
+async fn resolve<T: AddressFamily>(server: &str) -> Result<SocketAddr, Error> {
+    let target = if server.ends_with(']') || !server.contains(':') {
+        format!("{server}:{STUN_PORT}")
+    } else {
+        server.to_owned()
+    };
+    lookup_host(target)
+        .await?
+        .find(|addr| T::from_ip(addr.ip()).is_some())
+        .ok_or_else(|| Error::stun(format!("failed to resolve stun server {server}")))
+}
+
+async fn binding(socket: &UdpSocket, server: SocketAddr) -> Result<SocketAddr, Error> {
+    let mut transaction = [0u8; 12];
+    OsRng.fill_bytes(&mut transaction);
+
+    let mut request = Vec::with_capacity(HEADER_LEN);
+    request.extend_from_slice(&BINDING_REQUEST.to_be_bytes());
+    request.extend_from_slice(&0u16.to_be_bytes());
+    request.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    request.extend_from_slice(&transaction);
+
+    let mut buf = vec![0u8; 1500];
+    for _ in 0..ATTEMPTS {
+        socket.send_to(&request, server).await?;
+        let receive = async {
+            loop {
+                let (len, from) = socket.recv_from(&mut buf).await?;
+                if from != server {
+                    continue;
+                }
+                if let Some(mapped) = parse_response(&buf[..len], &transaction)? {
+                    return Ok::<_, Error>(mapped);
+                }
+            }
+        };
+        if let Ok(result) = timeout(TIMEOUT, receive).await {
+            return result;
+        }
+    }
+    Err(Error::stun(format!("stun request to {server} timed out")))
+}
+
+/// Returns the mapped address of a binding response, `None` when the message
+/// belongs to another transaction.
+fn parse_response(message: &[u8], transaction: &[u8; 12]) -> Result<Option<SocketAddr>, Error> {
+    if message.len() < HEADER_LEN
+        || message[4..8] != MAGIC_COOKIE.to_be_bytes()
+        || message[8..20] != transaction[..]
+    {
+        return Ok(None);
+    }
+    let kind = u16::from_be_bytes([message[0], message[1]]);
+    if kind != BINDING_RESPONSE {
+        return Err(Error::stun(format!(
+            "unexpected stun message type {kind:#06x}"
+        )));
+    }
+
+    let mut mapped = None;
+    let mut rest = &message[HEADER_LEN..];
+    while rest.len() >= 4 {
+        let attr = u16::from_be_bytes([rest[0], rest[1]]);
+        let len = u16::from_be_bytes([rest[2], rest[3]]) as usize;
+        let Some(value) = rest.get(4..4 + len) else {
+            break;
+        };
+        match attr {
+            ATTR_XOR_MAPPED_ADDRESS => return Ok(decode_address(value, Some(transaction))),
+            ATTR_MAPPED_ADDRESS => mapped = decode_address(value, None),
+            _ => {}
+        }
+        let padded = (4 + len).next_multiple_of(4);
+        rest = rest.get(padded..).unwrap_or_default();
+    }
+    mapped
+        .map(Some)
+        .ok_or_else(|| Error::stun("binding response without a mapped address"))
+}
+
+/// Decodes a (XOR-)MAPPED-ADDRESS value; `transaction` is set for the XOR form.
+fn decode_address(value: &[u8], transaction: Option<&[u8; 12]>) -> Option<SocketAddr> {
+    let family = *value.get(1)?;
+    let mut port = u16::from_be_bytes([*value.get(2)?, *value.get(3)?]);
+    let mut mask = [0u8; 16];
+    if let Some(transaction) = transaction {
+        port ^= (MAGIC_COOKIE >> 16) as u16;
+        mask[..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        mask[4..].copy_from_slice(transaction);
+    }
+    let ip = match family {
+        0x01 => {
+            let mut octets: [u8; 4] = value.get(4..8)?.try_into().ok()?;
+            octets.iter_mut().zip(mask).for_each(|(b, m)| *b ^= m);
+            IpAddr::V4(Ipv4Addr::from(octets))
+        }
+        0x02 => {
+            let mut octets: [u8; 16] = value.get(4..20)?.try_into().ok()?;
+            octets.iter_mut().zip(mask).for_each(|(b, m)| *b ^= m);
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 5769 section 2.2 and 2.3, without the integrity and fingerprint
+    // attributes that follow the address.
+    const TRANSACTION: [u8; 12] = [
+        0xb7, 0xe7, 0xa7, 0x01, 0xbc, 0x34, 0xd6, 0x86, 0xfa, 0x87, 0xdf, 0xae,
+    ];
+    const SOFTWARE: &[u8] = b"\x80\x22\x00\x0btest vector\x20";
+
+    fn response(kind: u16, attributes: &[&[u8]]) -> Vec<u8> {
+        let body = attributes.concat();
+        let mut message = kind.to_be_bytes().to_vec();
+        message.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        message.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        message.extend_from_slice(&TRANSACTION);
+        message.extend_from_slice(&body);
+        message
+    }
+
+    /// Answers binding requests with the sender's address, its port shifted
+    /// by `shift` to stand in for a NAT.
+    async fn server(shift: u16) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0; 1500];
+            loop {
+                let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+                let request = &buf[..len];
+                let IpAddr::V4(ip) = peer.ip() else {
+                    unreachable!()
+                };
+                let mut attribute = vec![0x00, 0x20, 0x00, 0x08, 0x00, 0x01];
+                let port = peer.port().wrapping_add(shift) ^ (MAGIC_COOKIE >> 16) as u16;
+                attribute.extend_from_slice(&port.to_be_bytes());
+                attribute.extend_from_slice(&(u32::from(ip) ^ MAGIC_COOKIE).to_be_bytes());
+
+                let mut response = BINDING_RESPONSE.to_be_bytes().to_vec();
+                response.extend_from_slice(&(attribute.len() as u16).to_be_bytes());
+                response.extend_from_slice(&request[4..HEADER_LEN]);
+                response.extend_from_slice(&attribute);
+                socket.send_to(&response, peer).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn decodes_rfc5769_ipv4_response() {
+        let message = response(
+            BINDING_RESPONSE,
+            &[
+                SOFTWARE,
+                &[
+                    0x00, 0x20, 0x00, 0x08, 0x00, 0x01, 0xa1, 0x47, 0xe1, 0x12, 0xa6, 0x43,
+                ],
+            ],
+        );
+        let mapped = parse_response(&message, &TRANSACTION).unwrap();
+        assert_eq!(mapped, Some("192.0.2.1:32853".parse().unwrap()));
+    }
+
+    #[test]
+    fn decodes_rfc5769_ipv6_response() {
+        let message = response(
+            BINDING_RESPONSE,
+            &[
+                SOFTWARE,
+                &[
+                    0x00, 0x20, 0x00, 0x14, 0x00, 0x02, 0xa1, 0x47, 0x01, 0x13, 0xa9, 0xfa, 0xa5,
+                    0xd3, 0xf1, 0x79, 0xbc, 0x25, 0xf4, 0xb5, 0xbe, 0xd2, 0xb9, 0xd9,
+                ],
+            ],
+        );
+        let mapped = parse_response(&message, &TRANSACTION).unwrap();
+        assert_eq!(
+            mapped,
+            Some(
+                "[2001:db8:1234:5678:11:2233:4455:6677]:32853"
+                    .parse()
+                    .unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn falls_back_to_mapped_address() {
+        let message = response(
+            BINDING_RESPONSE,
+            &[&[0x00, 0x01, 0x00, 0x08, 0x00, 0x01, 0x80, 0x55, 192, 0, 2, 1]],
+        );
+        let mapped = parse_response(&message, &TRANSACTION).unwrap();
+        assert_eq!(mapped, Some("192.0.2.1:32853".parse().unwrap()));
+    }
+
+    #[test]
+    fn ignores_other_transactions() {
+        let message = response(BINDING_RESPONSE, &[]);
+        let mut other = TRANSACTION;
+        other[0] ^= 0xff;
+        assert_eq!(parse_response(&message, &other).unwrap(), None);
+        assert_eq!(parse_response(&message[..12], &TRANSACTION).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_error_and_empty_responses() {
+        assert!(parse_response(&response(0x0111, &[]), &TRANSACTION).is_err());
+        assert!(parse_response(&response(BINDING_RESPONSE, &[SOFTWARE]), &TRANSACTION).is_err());
+        // An attribute running past the end of the message.
+        let truncated = response(BINDING_RESPONSE, &[&[0x00, 0x20, 0x00, 0x08, 0x00, 0x01]]);
+        assert!(parse_response(&truncated, &TRANSACTION).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_family() {
+        assert_eq!(decode_address(&[0, 3, 0, 0, 1, 2, 3, 4], None), None);
+        assert_eq!(decode_address(&[0, 1, 0, 0, 1, 2], None), None);
+    }
+
+    #[tokio::test]
+    async fn reports_mapped_address() {
+        let server = server(0).await.to_string();
+        let lookup = StunIpLookup::<Ipv4Addr>::new("lo", "", &server, None);
+        assert_eq!(lookup.lookup().await.unwrap(), Ipv4Addr::LOCALHOST);
+        assert!(lookup.take_warnings().is_empty());
+    }
+
+    #[tokio::test]
+    async fn detects_symmetric_nat() {
+        let first = server(0).await.to_string();
+        let second = server(1).await.to_string();
+        let lookup = StunIpLookup::<Ipv4Addr>::new("lo", "", &first, Some(&second));
+        assert_eq!(lookup.lookup().await.unwrap(), Ipv4Addr::LOCALHOST);
+
+        let warnings = lookup.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("symmetric NAT detected on lo"));
+    }
+}
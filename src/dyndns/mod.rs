@@ -1,7 +1,14 @@
 mod checker;
+mod dns;
+mod dry_run;
 mod http_client;
 mod lookup;
+mod provider;
 mod scheduler;
+mod trace;
 mod updater;
+mod verify;
 
-pub use scheduler::launch;
+pub use dry_run::{DryRunReport, dry_run};
+pub use scheduler::{CycleReport, SchedulerHandle, launch, trigger_channel};
+pub use updater::clear_records;
@@ -0,0 +1,601 @@
+use std::net::IpAddr;
+
+use isahc::{Request, http::Method, prelude::AsyncReadResponseExt};
+use serde::{
+    Deserialize, Serialize,
+    de::{DeserializeOwned, IgnoredAny},
+};
+
+use crate::{
+    Error,
+    db::{DynDNS, ProviderOptions},
+    error::ProviderError,
+};
+
+use super::super::{
+    checker::{ipv4::Ipv4CheckResult, ipv6::Ipv6CheckResult},
+    http_client::HttpClient,
+};
+use super::{DnsProvider, UpdateOutcome};
+
+const PROVIDER: &str = "cloudflare";
+
+pub struct CloudflareProvider<'a> {
+    client: &'a HttpClient,
+    api_url: String,
+    token: &'a str,
+    config: &'a DynDNS,
+    options: &'a ProviderOptions,
+}
+
+impl<'a> CloudflareProvider<'a> {
+    pub fn new(client: &'a HttpClient, config: &'a DynDNS) -> Self {
+        let api_url = match config.options.api_url.as_deref() {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => format!("https://{}/client/v4", config.server),
+        };
+        Self {
+            client,
+            api_url,
+            token: config.password.as_str(),
+            config,
+            options: &config.options,
+        }
+    }
+
+    async fn call<T>(&self, method: Method, path: &str, body: Option<Vec<u8>>) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let request = Request::builder()
+            .method(method)
+            .uri(format!("{}{}", self.api_url, path))
+            .header("authorization", format!("Bearer {}", self.token))
+            .header("content-type", "application/json")
+            .body(body.unwrap_or_default())
+            .map_err(|err| {
+                Error::validation_failed(format!("invalid {PROVIDER} request: {err}"))
+            })?;
+
+        let mut response = self.client.send_async(request).await?;
+        let status = response.status();
+        let body = response.text().await?;
+        let envelope: Envelope<T> = serde_json::from_str(&body).map_err(|err| {
+            Error::provider_invalid_response(PROVIDER, format!("code: {status}, {err}"))
+        })?;
+
+        if !envelope.success {
+            let message = envelope
+                .errors
+                .iter()
+                .map(|err| format!("{} ({})", err.message, err.code))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(Error::provider_api(PROVIDER, message));
+        }
+
+        envelope
+            .result
+            .ok_or_else(|| Error::provider_invalid_response(PROVIDER, "missing result"))
+    }
+
+    async fn zone_id(&self, hostname: &str) -> Result<String, Error> {
+        let candidates: Vec<&str> = match self.options.zone.as_deref() {
+            Some(zone) => vec![zone],
+            None => std::iter::once(hostname)
+                .chain(
+                    hostname
+                        .match_indices('.')
+                        .map(|(index, _)| &hostname[index + 1..])
+                        .filter(|zone| zone.contains('.')),
+                )
+                .collect(),
+        };
+
+        for zone in candidates {
+            let zones: Vec<Zone> = self
+                .call(Method::GET, &format!("/zones?name={zone}"), None)
+                .await?;
+            if let Some(found) = zones.into_iter().next() {
+                debug!("cloudflare zone {} ({})", found.name, found.id);
+                return Ok(found.id);
+            }
+        }
+
+        Err(ProviderError::ZoneNotFound {
+            provider: PROVIDER,
+            hostname: hostname.to_string(),
+        }
+        .into())
+    }
+
+    /// Makes the records of one type for `hostname` match `addresses`, reusing
+    /// stale records before creating new ones and deleting what is left over.
+    async fn sync(
+        &self,
+        zone_id: &str,
+        hostname: &str,
+        record_type: &str,
+        addresses: &[IpAddr],
+    ) -> Result<bool, Error> {
+        let records: Vec<DnsRecord> = self
+            .call(
+                Method::GET,
+                &format!("/zones/{zone_id}/dns_records?type={record_type}&name={hostname}"),
+                None,
+            )
+            .await?;
+
+        let published = |record: &DnsRecord| {
+            record
+                .content
+                .parse::<IpAddr>()
+                .is_ok_and(|address| addresses.contains(&address))
+        };
+        let (kept, mut stale): (Vec<_>, Vec<_>) = records.into_iter().partition(published);
+        let missing: Vec<&IpAddr> = addresses
+            .iter()
+            .filter(|address| {
+                !kept
+                    .iter()
+                    .any(|record| record.content.parse().ok().as_ref() == Some(*address))
+            })
+            .collect();
+
+        if missing.is_empty() && stale.is_empty() {
+            debug!(
+                "cloudflare {} records for {} already {}",
+                record_type,
+                hostname,
+                join(addresses)
+            );
+            return Ok(false);
+        }
+
+        for address in missing {
+            let content = address.to_string();
+            let payload = RecordPayload {
+                record_type,
+                name: hostname,
+                content: &content,
+                ttl: self.options.ttl,
+                proxied: self.options.proxied,
+            };
+            let body = serde_json::to_vec(&payload)
+                .map_err(|err| Error::provider_invalid_response(PROVIDER, err.to_string()))?;
+
+            let _record: DnsRecord = match stale.pop() {
+                Some(record) => {
+                    let path = format!("/zones/{zone_id}/dns_records/{}", record.id);
+                    self.call(Method::PATCH, &path, Some(body)).await?
+                }
+                None => {
+                    let path = format!("/zones/{zone_id}/dns_records");
+                    self.call(Method::POST, &path, Some(body)).await?
+                }
+            };
+        }
+        for record in stale {
+            let path = format!("/zones/{zone_id}/dns_records/{}", record.id);
+            let _deleted: IgnoredAny = self.call(Method::DELETE, &path, None).await?;
+        }
+
+        info!(
+            "cloudflare {} records for {} set to {}",
+            record_type,
+            hostname,
+            join(addresses)
+        );
+        Ok(true)
+    }
+}
+
+fn join(addresses: &[IpAddr]) -> String {
+    addresses
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl<'a> DnsProvider for CloudflareProvider<'a> {
+    async fn update(
+        &self,
+        ipv4: &Ipv4CheckResult,
+        ipv6: &Ipv6CheckResult,
+    ) -> Result<UpdateOutcome, Error> {
+        let mut changed = false;
+
+        for hostname in self.config.hostnames() {
+            let zone_id = self.zone_id(hostname).await?;
+            if let Some(address) = ipv4.external {
+                changed |= self
+                    .sync(&zone_id, hostname, "A", &[IpAddr::V4(address)])
+                    .await?;
+            }
+            if let Some(addresses) = ipv6.external.as_ref() {
+                let addresses: Vec<IpAddr> = addresses.iter().copied().map(IpAddr::V6).collect();
+                changed |= self.sync(&zone_id, hostname, "AAAA", &addresses).await?;
+            }
+        }
+
+        Ok(if changed {
+            UpdateOutcome::Updated
+        } else {
+            UpdateOutcome::Unchanged
+        })
+    }
+
+    /// Looks the zone and the current records of every hostname up.
+    async fn probe(&self) -> Result<Option<String>, Error> {
+        let mut found = vec![];
+        for hostname in self.config.hostnames() {
+            let zone_id = self.zone_id(hostname).await?;
+            let records: Vec<DnsRecord> = self
+                .call(
+                    Method::GET,
+                    &format!("/zones/{zone_id}/dns_records?name={hostname}"),
+                    None,
+                )
+                .await?;
+            let records = records
+                .iter()
+                .map(|record| format!("{} {}", record.record_type, record.content))
+                .collect::<Vec<_>>();
+            if records.is_empty() {
+                found.push(format!("{hostname}: no records"));
+            } else {
+                found.push(format!("{hostname}: {}", records.join(", ")));
+            }
+        }
+        Ok(Some(found.join("; ")))
+    }
+}
+
+#[derive(Deserialize)]
+struct Envelope<T> {
+    success: bool,
+    #[serde(default)]
+    errors: Vec<ApiMessage>,
+    result: Option<T>,
+}
+
+#[derive(Deserialize)]
+struct ApiMessage {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct Zone {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct DnsRecord {
+    id: String,
+    #[serde(rename = "type")]
+    record_type: String,
+    content: String,
+}
+
+#[derive(Serialize)]
+struct RecordPayload<'a> {
+    #[serde(rename = "type")]
+    record_type: &'a str,
+    name: &'a str,
+    content: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ttl: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proxied: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::http::{Method, StatusCode};
+    use serde_json::{Value, json};
+
+    use crate::error::DynDnsError;
+
+    use super::super::mock::{MockServer, Reply};
+    use super::*;
+
+    fn config(server: &MockServer, options: Value) -> DynDNS {
+        let mut options = options;
+        options["api_url"] = json!(format!("{}/client/v4", server.url));
+        serde_json::from_value(json!({
+            "server": "api.cloudflare.com",
+            "username": "",
+            "password": "token",
+            "hostname": "home.example.com",
+            "ip": 3,
+            "interface": "lo",
+            "provider": "cloudflare",
+            "options": options,
+        }))
+        .unwrap()
+    }
+
+    fn ok(result: Value) -> Value {
+        json!({ "success": true, "errors": [], "result": result })
+    }
+
+    fn record(id: &str, record_type: &str, content: &str) -> Value {
+        json!({ "id": id, "type": record_type, "name": "home.example.com", "content": content })
+    }
+
+    fn ipv4(address: &str) -> Ipv4CheckResult {
+        Ipv4CheckResult {
+            external: Some(address.parse().unwrap()),
+            ..Default::default()
+        }
+    }
+
+    fn ipv6(addresses: &[&str]) -> Ipv6CheckResult {
+        Ipv6CheckResult {
+            external: Some(addresses.iter().map(|a| a.parse().unwrap()).collect()),
+            ..Default::default()
+        }
+    }
+
+    async fn update(
+        config: &DynDNS,
+        ipv4: &Ipv4CheckResult,
+        ipv6: &Ipv6CheckResult,
+    ) -> Result<UpdateOutcome, Error> {
+        let client = HttpClient::new(1, Duration::ZERO);
+        CloudflareProvider::new(&client, config)
+            .update(ipv4, ipv6)
+            .await
+    }
+
+    #[tokio::test]
+    async fn finds_zone_of_parent_domain_and_creates_record() {
+        let server = MockServer::start(vec![
+            Reply::json(
+                Method::GET,
+                "/client/v4/zones?name=home.example.com",
+                ok(json!([])),
+            ),
+            Reply::json(
+                Method::GET,
+                "/client/v4/zones?name=example.com",
+                ok(json!([{ "id": "zone", "name": "example.com" }])),
+            ),
+            Reply::json(
+                Method::GET,
+                "/client/v4/zones/zone/dns_records?type=A&name=home.example.com",
+                ok(json!([])),
+            ),
+            Reply::json(
+                Method::POST,
+                "/client/v4/zones/zone/dns_records",
+                ok(record("new", "A", "203.0.113.7")),
+            ),
+        ])
+        .await;
+        let config = config(&server, json!({ "ttl": 120, "proxied": true }));
+
+        let outcome = update(&config, &ipv4("203.0.113.7"), &Default::default()).await;
+        assert_eq!(outcome.unwrap(), UpdateOutcome::Updated);
+
+        let received = server.received();
+        assert_eq!(received.len(), 4);
+        assert!(
+            received
+                .iter()
+                .all(|request| request.headers["authorization"] == "Bearer token")
+        );
+        let created = &received[3];
+        assert_eq!(created.method, Method::POST);
+        assert_eq!(
+            created.json(),
+            json!({
+                "type": "A",
+                "name": "home.example.com",
+                "content": "203.0.113.7",
+                "ttl": 120,
+                "proxied": true,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn patches_stale_record_in_configured_zone() {
+        let server = MockServer::start(vec![
+            Reply::json(
+                Method::GET,
+                "/client/v4/zones?name=example.com",
+                ok(json!([{ "id": "zone", "name": "example.com" }])),
+            ),
+            Reply::json(
+                Method::GET,
+                "/client/v4/zones/zone/dns_records?type=A&name=home.example.com",
+                ok(json!([record("old", "A", "198.51.100.1")])),
+            ),
+            Reply::json(
+                Method::PATCH,
+                "/client/v4/zones/zone/dns_records/old",
+                ok(record("old", "A", "203.0.113.7")),
+            ),
+        ])
+        .await;
+        let config = config(&server, json!({ "zone": "example.com" }));
+
+        let outcome = update(&config, &ipv4("203.0.113.7"), &Default::default()).await;
+        assert_eq!(outcome.unwrap(), UpdateOutcome::Updated);
+
+        let received = server.received();
+        assert_eq!(received.len(), 3);
+        assert_eq!(received[2].method, Method::PATCH);
+        assert_eq!(received[2].uri, "/client/v4/zones/zone/dns_records/old");
+        // Neither ttl nor proxied is sent unless configured.
+        assert_eq!(
+            received[2].json(),
+            json!({ "type": "A", "name": "home.example.com", "content": "203.0.113.7" })
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_current_records_and_deletes_surplus_ones() {
+        let server = MockServer::start(vec![
+            Reply::json(
+                Method::GET,
+                "/client/v4/zones?name=example.com",
+                ok(json!([{ "id": "zone", "name": "example.com" }])),
+            ),
+            Reply::json(
+                Method::GET,
+                "/client/v4/zones/zone/dns_records?type=AAAA&name=home.example.com",
+                ok(json!([
+                    record("current", "AAAA", "2001:db8::1"),
+                    record("surplus", "AAAA", "2001:db8::2"),
+                ])),
+            ),
+            Reply::json(
+                Method::DELETE,
+                "/client/v4/zones/zone/dns_records/surplus",
+                ok(json!({ "id": "surplus" })),
+            ),
+        ])
+        .await;
+        let config = config(&server, json!({ "zone": "example.com" }));
+
+        let outcome = update(&config, &Default::default(), &ipv6(&["2001:db8::1"])).await;
+        assert_eq!(outcome.unwrap(), UpdateOutcome::Updated);
+
+        let received = server.received();
+        assert_eq!(received.len(), 3);
+        assert_eq!(received[2].method, Method::DELETE);
+        assert_eq!(received[2].uri, "/client/v4/zones/zone/dns_records/surplus");
+    }
+
+    #[tokio::test]
+    async fn leaves_matching_record_alone() {
+        let server = MockServer::start(vec![
+            Reply::json(
+                Method::GET,
+                "/client/v4/zones?name=example.com",
+                ok(json!([{ "id": "zone", "name": "example.com" }])),
+            ),
+            Reply::json(
+                Method::GET,
+                "/client/v4/zones/zone/dns_records?type=A&name=home.example.com",
+                ok(json!([record("current", "A", "203.0.113.7")])),
+            ),
+        ])
+        .await;
+        let config = config(&server, json!({ "zone": "example.com" }));
+
+        let outcome = update(&config, &ipv4("203.0.113.7"), &Default::default()).await;
+        assert_eq!(outcome.unwrap(), UpdateOutcome::Unchanged);
+        assert!(
+            server
+                .received()
+                .iter()
+                .all(|request| request.method == Method::GET)
+        );
+    }
+
+    #[tokio::test]
+    async fn maps_api_errors() {
+        let server = MockServer::start(vec![
+            Reply::json(
+                Method::GET,
+                "/client/v4/zones?name=example.com",
+                json!({
+                    "success": false,
+                    "errors": [
+                        { "code": 9109, "message": "Invalid access token" },
+                        { "code": 10000, "message": "Authentication error" },
+                    ],
+                    "result": null,
+                }),
+            )
+            .status(StatusCode::FORBIDDEN),
+        ])
+        .await;
+        let config = config(&server, json!({ "zone": "example.com" }));
+
+        let err = update(&config, &ipv4("203.0.113.7"), &Default::default())
+            .await
+            .unwrap_err();
+        let Error::DynDns(DynDnsError::Provider(ProviderError::Api { provider, message })) = err
+        else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(provider, PROVIDER);
+        assert_eq!(
+            message,
+            "Invalid access token (9109), Authentication error (10000)"
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_missing_zone() {
+        let server = MockServer::start(vec![
+            Reply::json(
+                Method::GET,
+                "/client/v4/zones?name=home.example.com",
+                ok(json!([])),
+            ),
+            Reply::json(
+                Method::GET,
+                "/client/v4/zones?name=example.com",
+                ok(json!([])),
+            ),
+        ])
+        .await;
+        let config = config(&server, json!({}));
+
+        let err = update(&config, &ipv4("203.0.113.7"), &Default::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::DynDns(DynDnsError::Provider(ProviderError::ZoneNotFound { ref hostname, .. }))
+                if hostname == "home.example.com"
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_body_that_is_not_an_envelope() {
+        let server = MockServer::start(vec![
+            Reply::text(
+                Method::GET,
+                "/client/v4/zones?name=example.com",
+                "bad gateway",
+            )
+            .status(StatusCode::BAD_GATEWAY),
+        ])
+        .await;
+        let config = config(&server, json!({ "zone": "example.com" }));
+
+        let err = update(&config, &ipv4("203.0.113.7"), &Default::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::DynDns(DynDnsError::Provider(ProviderError::InvalidResponse { .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_zone_that_is_not_a_valid_uri() {
+        let server = MockServer::start(vec![]).await;
+        let config = config(&server, json!({ "zone": "example .com" }));
+
+        let err = update(&config, &ipv4("203.0.113.7"), &Default::default())
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().starts_with("invalid cloudflare request"),
+            "{err}"
+        );
+        assert!(server.received().is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+use isahc::{Request, http::StatusCode, prelude::AsyncReadResponseExt};
+
+use crate::{Error, db::DynDNS, util::percent_encode};
+
+use super::super::{
+    checker::{ipv4::Ipv4CheckResult, ipv6::Ipv6CheckResult},
+    http_client::HttpClient,
+};
+use super::{DnsProvider, UpdateOutcome};
+
+const PROVIDER: &str = "duckdns";
+const DOMAIN_SUFFIX: &str = ".duckdns.org";
+
+/// Updates every subdomain in one token-authenticated request.
+pub struct DuckDnsProvider<'a> {
+    client: &'a HttpClient,
+    api_url: String,
+    token: &'a str,
+    domains: String,
+}
+
+impl<'a> DuckDnsProvider<'a> {
+    pub fn new(client: &'a HttpClient, config: &'a DynDNS) -> Self {
+        let api_url = match config.options.api_url.as_deref() {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => format!("https://{}/update", config.server),
+        };
+        let domains = config
+            .hostnames()
+            .map(|hostname| hostname.strip_suffix(DOMAIN_SUFFIX).unwrap_or(hostname))
+            .collect::<Vec<_>>()
+            .join(",");
+        Self {
+            client,
+            api_url,
+            token: config.password.as_str(),
+            domains,
+        }
+    }
+
+    async fn call(&self, params: &[(&str, String)]) -> Result<Reply, Error> {
+        let mut url = format!(
+            "{}?domains={}&token={}",
+            self.api_url,
+            percent_encode(&self.domains),
+            percent_encode(self.token)
+        );
+        for (name, value) in params {
+            url.push_str(&format!("&{}={}", name, percent_encode(value)));
+        }
+        url.push_str("&verbose=true");
+
+        let request = Request::get(url).body(()).unwrap();
+        let mut response = self.client.send_async(request).await?;
+        let status = response.status();
+        let body = response.text().await?;
+        let message = body.trim();
+        debug!("code: {status}, msg: {message}");
+
+        Reply::parse(status, message)
+    }
+}
+
+impl<'a> DnsProvider for DuckDnsProvider<'a> {
+    async fn update(
+        &self,
+        ipv4: &Ipv4CheckResult,
+        ipv6: &Ipv6CheckResult,
+    ) -> Result<UpdateOutcome, Error> {
+        let mut params = vec![];
+        if let Some(address) = ipv4.external {
+            params.push(("ip", address.to_string()));
+        }
+        if let Some(address) = ipv6.external.as_ref().and_then(|addrs| addrs.first()) {
+            params.push(("ipv6", address.to_string()));
+        }
+        if params.is_empty() {
+            return Ok(UpdateOutcome::Unchanged);
+        }
+
+        Ok(match self.call(&params).await? {
+            Reply::Ok { changed: true } => UpdateOutcome::Updated,
+            Reply::Ok { changed: false } => UpdateOutcome::Unchanged,
+            Reply::Ko => UpdateOutcome::Fatal(Reply::rejected().to_string()),
+        })
+    }
+
+    async fn clear(&self) -> Result<Option<String>, Error> {
+        match self.call(&[("clear", "true".to_string())]).await? {
+            Reply::Ok { .. } => Ok(Some(format!("cleared {}", self.domains))),
+            Reply::Ko => Err(Reply::rejected()),
+        }
+    }
+}
+
+/// First line of a verbose reply; `UPDATED` or `NOCHANGE` ends a successful one.
+enum Reply {
+    Ok { changed: bool },
+    Ko,
+}
+
+impl Reply {
+    fn parse(status: StatusCode, message: &str) -> Result<Self, Error> {
+        match message.lines().next().map(str::trim) {
+            Some("OK") => Ok(Self::Ok {
+                changed: message.lines().last().map(str::trim) != Some("NOCHANGE"),
+            }),
+            Some("KO") => Ok(Self::Ko),
+            _ => Err(Error::provider_invalid_response(
+                PROVIDER,
+                format!("code: {status}, {message}"),
+            )),
+        }
+    }
+
+    /// DuckDNS answers KO for an unknown token as well as for a subdomain the
+    /// token does not own, without telling which.
+    fn rejected() -> Error {
+        Error::provider_rejected(PROVIDER, "token or domains not accepted")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::http::Method;
+    use serde_json::json;
+
+    use crate::error::{DynDnsError, ProviderError};
+
+    use super::super::mock::{MockServer, Reply as MockReply};
+    use super::*;
+
+    fn parse(message: &str) -> Result<Reply, Error> {
+        Reply::parse(StatusCode::OK, message)
+    }
+
+    #[test]
+    fn parses_ok_replies() {
+        assert!(matches!(parse("OK"), Ok(Reply::Ok { changed: true })));
+        assert!(matches!(
+            parse("OK\n203.0.113.7\n2001:db8::1\nUPDATED"),
+            Ok(Reply::Ok { changed: true })
+        ));
+        assert!(matches!(
+            parse("OK\r\n203.0.113.7\r\n\r\nNOCHANGE"),
+            Ok(Reply::Ok { changed: false })
+        ));
+    }
+
+    #[test]
+    fn parses_ko_reply() {
+        assert!(matches!(parse("KO"), Ok(Reply::Ko)));
+        assert!(matches!(
+            Reply::rejected(),
+            Error::DynDns(DynDnsError::Provider(ProviderError::Rejected { .. }))
+        ));
+    }
+
+    #[test]
+    fn rejects_anything_else() {
+        for message in ["", "ok", "<html>Bad Gateway</html>"] {
+            let err = Reply::parse(StatusCode::BAD_GATEWAY, message)
+                .err()
+                .unwrap();
+            assert_eq!(
+                err.to_string(),
+                format!("duckdns: unexpected response: code: 502 Bad Gateway, {message}")
+            );
+        }
+    }
+
+    async fn server(uri: &str, body: &str) -> (MockServer, DynDNS) {
+        let server = MockServer::start(vec![MockReply::text(Method::GET, uri, body)]).await;
+        let config = serde_json::from_value(json!({
+            "server": "www.duckdns.org",
+            "username": "",
+            "password": "a7c4d0ad-114e-40ef-ba1d-d217904a50f2",
+            "hostname": "home.duckdns.org, work",
+            "ip": 3,
+            "interface": "lo",
+            "provider": "duckdns",
+            "options": { "api_url": format!("{}/update/", server.url) },
+        }))
+        .unwrap();
+        (server, config)
+    }
+
+    #[tokio::test]
+    async fn updates_all_domains_in_one_request() {
+        let (server, config) = server(
+            "/update?domains=home%2Cwork&token=a7c4d0ad-114e-40ef-ba1d-d217904a50f2\
+             &ip=203.0.113.7&ipv6=2001%3Adb8%3A%3A1&verbose=true",
+            "OK\n203.0.113.7\n2001:db8::1\nNOCHANGE",
+        )
+        .await;
+        let client = HttpClient::new(1, Duration::ZERO);
+        let ipv4 = Ipv4CheckResult {
+            external: Some("203.0.113.7".parse().unwrap()),
+            ..Default::default()
+        };
+        let ipv6 = Ipv6CheckResult {
+            external: Some(vec!["2001:db8::1".parse().unwrap()]),
+            ..Default::default()
+        };
+
+        let outcome = DuckDnsProvider::new(&client, &config)
+            .update(&ipv4, &ipv6)
+            .await;
+        assert_eq!(outcome.unwrap(), UpdateOutcome::Unchanged);
+        assert_eq!(server.received().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn reports_rejected_clear() {
+        let (_server, config) = server(
+            "/update?domains=home%2Cwork&token=a7c4d0ad-114e-40ef-ba1d-d217904a50f2\
+             &clear=true&verbose=true",
+            "KO",
+        )
+        .await;
+        let client = HttpClient::new(1, Duration::ZERO);
+
+        let err = DuckDnsProvider::new(&client, &config)
+            .clear()
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "duckdns: request rejected: token or domains not accepted"
+        );
+    }
+}
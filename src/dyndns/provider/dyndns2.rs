@@ -0,0 +1,342 @@
+use std::{
+    fmt::Display,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use isahc::{
+    Request,
+    auth::{Authentication, Credentials},
+    config::Configurable,
+    prelude::AsyncReadResponseExt,
+};
+use serde::{Serialize, Serializer};
+
+use crate::{Error, util::percent_encode};
+
+use crate::db::DynDNS;
+
+use super::super::{
+    checker::{ipv4::Ipv4CheckResult, ipv6::Ipv6CheckResult},
+    http_client::HttpClient,
+};
+use super::{DnsProvider, UpdateOutcome};
+
+pub struct Dyndns2Provider<'a> {
+    client: &'a HttpClient,
+    auth: DynDnsAuth<'a>,
+}
+
+impl<'a> Dyndns2Provider<'a> {
+    pub fn new(client: &'a HttpClient, auth: DynDnsAuth<'a>) -> Self {
+        Self { client, auth }
+    }
+
+    fn request(
+        &self,
+        ipv4: &Ipv4CheckResult,
+        ipv6: &Ipv6CheckResult,
+    ) -> Result<Request<()>, isahc::http::Error> {
+        let myip = MyIp::new(ipv4.external.as_ref(), ipv6.external.as_deref());
+        let params = DynDnsParams::new(&self.auth.hostname, myip);
+
+        let url = format!(
+            "https://{server}/nic/update?hostname={hostname}&myip={myip}",
+            server = self.auth.server,
+            hostname = percent_encode(params.hostname),
+            myip = percent_encode(&params.myip.to_string())
+        );
+
+        Request::get(url)
+            .authentication(Authentication::basic())
+            .credentials(Credentials::new(self.auth.username, self.auth.password))
+            .body(())
+    }
+}
+
+impl<'a> DnsProvider for Dyndns2Provider<'a> {
+    async fn update(
+        &self,
+        ipv4: &Ipv4CheckResult,
+        ipv6: &Ipv6CheckResult,
+    ) -> Result<UpdateOutcome, Error> {
+        let request = match self.request(ipv4, ipv6) {
+            Ok(request) => request,
+            Err(err) => {
+                return Ok(UpdateOutcome::Fatal(format!(
+                    "invalid update request: {err}"
+                )));
+            }
+        };
+
+        let mut response = self.client.send_async(request).await?;
+        let status = response.status();
+        let body = response.text().await?;
+        let message = body.trim();
+        debug!("code: {status}, msg: {message}");
+
+        let outcome = parse_reply(message);
+        if let UpdateOutcome::Failed(reason) = &outcome
+            && !status.is_success()
+        {
+            return Ok(UpdateOutcome::Failed(format!("code: {status}, {reason}")));
+        }
+        Ok(outcome)
+    }
+}
+
+/// Takes the worst of the return codes, one per line for each hostname.
+fn parse_reply(message: &str) -> UpdateOutcome {
+    message
+        .lines()
+        .map(ReturnCode::parse)
+        .map(UpdateOutcome::from)
+        .reduce(|worst, outcome| {
+            if outcome.severity() > worst.severity() {
+                outcome
+            } else {
+                worst
+            }
+        })
+        .unwrap_or_else(|| UpdateOutcome::Failed("empty response".to_string()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReturnCode<'a> {
+    Good,
+    NoChange,
+    BadAuth,
+    NotFqdn,
+    NoHost,
+    NumHost,
+    Abuse,
+    BadAgent,
+    NotDonator,
+    DnsError,
+    ServerError,
+    Unknown(&'a str),
+}
+
+impl<'a> ReturnCode<'a> {
+    fn parse(line: &'a str) -> Self {
+        let line = line.trim();
+        match line.split_whitespace().next().unwrap_or_default() {
+            "good" => Self::Good,
+            "nochg" => Self::NoChange,
+            "badauth" => Self::BadAuth,
+            "notfqdn" => Self::NotFqdn,
+            "nohost" => Self::NoHost,
+            "numhost" => Self::NumHost,
+            "abuse" => Self::Abuse,
+            "badagent" => Self::BadAgent,
+            "!donator" => Self::NotDonator,
+            "dnserr" => Self::DnsError,
+            "911" => Self::ServerError,
+            _ => Self::Unknown(line),
+        }
+    }
+
+    fn as_str(&self) -> &'a str {
+        match self {
+            Self::Good => "good",
+            Self::NoChange => "nochg",
+            Self::BadAuth => "badauth",
+            Self::NotFqdn => "notfqdn",
+            Self::NoHost => "nohost",
+            Self::NumHost => "numhost",
+            Self::Abuse => "abuse",
+            Self::BadAgent => "badagent",
+            Self::NotDonator => "!donator",
+            Self::DnsError => "dnserr",
+            Self::ServerError => "911",
+            Self::Unknown(line) => line,
+        }
+    }
+}
+
+impl<'a> From<ReturnCode<'a>> for UpdateOutcome {
+    fn from(code: ReturnCode<'a>) -> Self {
+        let reason = code.as_str().to_string();
+        match code {
+            ReturnCode::Good => UpdateOutcome::Updated,
+            ReturnCode::NoChange => UpdateOutcome::Unchanged,
+            ReturnCode::BadAuth
+            | ReturnCode::NotFqdn
+            | ReturnCode::NoHost
+            | ReturnCode::NumHost
+            | ReturnCode::Abuse
+            | ReturnCode::BadAgent
+            | ReturnCode::NotDonator => UpdateOutcome::Fatal(reason),
+            ReturnCode::DnsError | ReturnCode::ServerError => UpdateOutcome::RetryLater(reason),
+            ReturnCode::Unknown(_) => UpdateOutcome::Failed(reason),
+        }
+    }
+}
+
+#[derive(Default)]
+struct MyIp<'a> {
+    v4: Option<&'a Ipv4Addr>,
+    v6: Option<&'a [Ipv6Addr]>,
+}
+
+impl<'a> MyIp<'a> {
+    fn new(v4: Option<&'a Ipv4Addr>, v6: Option<&'a [Ipv6Addr]>) -> Self {
+        Self { v4, v6 }
+    }
+
+    fn serialize<S>(value: &Self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(value.to_string().as_str())
+    }
+}
+
+impl<'a> Display for MyIp<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = vec![];
+        if let Some(ip) = self.v4 {
+            parts.push(ip.to_string());
+        }
+        if let Some(ips) = self.v6 {
+            parts.extend(ips.iter().map(ToString::to_string));
+        }
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+#[derive(Serialize)]
+struct DynDnsParams<'a, 'b> {
+    hostname: &'a str,
+    #[serde(serialize_with = "MyIp::serialize")]
+    myip: MyIp<'b>,
+}
+
+impl<'a, 'b> DynDnsParams<'a, 'b> {
+    fn new(hostname: &'a str, myip: MyIp<'b>) -> Self {
+        Self { hostname, myip }
+    }
+}
+
+pub struct DynDnsAuth<'a> {
+    pub server: &'a str,
+    pub username: &'a str,
+    pub password: &'a str,
+    /// Comma separated, without the spaces the configuration allows.
+    pub hostname: String,
+}
+
+impl<'a> From<&'a DynDNS> for DynDnsAuth<'a> {
+    fn from(value: &'a DynDNS) -> Self {
+        Self {
+            server: value.server.as_str(),
+            username: value.username.as_str(),
+            password: value.password.as_str(),
+            hostname: value.hostnames().collect::<Vec<_>>().join(","),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_return_codes() {
+        assert_eq!(ReturnCode::parse("good 203.0.113.7"), ReturnCode::Good);
+        assert_eq!(ReturnCode::parse("nochg 203.0.113.7"), ReturnCode::NoChange);
+        assert_eq!(ReturnCode::parse(" badauth\r"), ReturnCode::BadAuth);
+        assert_eq!(ReturnCode::parse("!donator"), ReturnCode::NotDonator);
+        assert_eq!(ReturnCode::parse("911"), ReturnCode::ServerError);
+        assert_eq!(
+            ReturnCode::parse("Good 203.0.113.7"),
+            ReturnCode::Unknown("Good 203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn maps_return_codes_to_outcomes() {
+        let outcome = |line| UpdateOutcome::from(ReturnCode::parse(line));
+        assert_eq!(outcome("good 203.0.113.7"), UpdateOutcome::Updated);
+        assert_eq!(outcome("nochg 203.0.113.7"), UpdateOutcome::Unchanged);
+        assert_eq!(outcome("911"), UpdateOutcome::RetryLater("911".to_string()));
+        assert_eq!(
+            outcome("dnserr"),
+            UpdateOutcome::RetryLater("dnserr".to_string())
+        );
+        assert_eq!(
+            outcome("!donator"),
+            UpdateOutcome::Fatal("!donator".to_string())
+        );
+        for code in [
+            "badauth", "notfqdn", "nohost", "numhost", "abuse", "badagent",
+        ] {
+            assert_eq!(outcome(code), UpdateOutcome::Fatal(code.to_string()));
+        }
+        assert_eq!(
+            outcome("<html>"),
+            UpdateOutcome::Failed("<html>".to_string())
+        );
+    }
+
+    #[test]
+    fn takes_worst_code_of_all_hostnames() {
+        assert_eq!(parse_reply("nochg 203.0.113.7"), UpdateOutcome::Unchanged);
+        assert_eq!(
+            parse_reply("nochg 203.0.113.7\ngood 203.0.113.7"),
+            UpdateOutcome::Updated
+        );
+        assert_eq!(
+            parse_reply("good 203.0.113.7\n911\nnohost"),
+            UpdateOutcome::Fatal("nohost".to_string())
+        );
+        assert_eq!(
+            parse_reply(""),
+            UpdateOutcome::Failed("empty response".to_string())
+        );
+    }
+
+    #[test]
+    fn encodes_hostnames_and_addresses() {
+        let config: DynDNS = serde_json::from_value(serde_json::json!({
+            "server": "members.example.com",
+            "username": "user",
+            "password": "password",
+            "hostname": "a.example.com, b.example.com",
+            "ip": 3,
+            "interface": "lo",
+        }))
+        .unwrap();
+        let client = HttpClient::new(1, std::time::Duration::ZERO);
+        let provider = Dyndns2Provider::new(&client, DynDnsAuth::from(&config));
+        let ipv4 = Ipv4CheckResult {
+            external: Some("203.0.113.7".parse().unwrap()),
+            ..Default::default()
+        };
+        let ipv6 = Ipv6CheckResult {
+            external: Some(vec!["2001:db8::1".parse().unwrap()]),
+            ..Default::default()
+        };
+
+        let request = provider.request(&ipv4, &ipv6).unwrap();
+        assert_eq!(
+            request.uri().to_string(),
+            "https://members.example.com/nic/update?hostname=a.example.com%2Cb.example.com\
+             &myip=203.0.113.7%2C2001%3Adb8%3A%3A1"
+        );
+    }
+
+    #[test]
+    fn joins_addresses_for_myip() {
+        let v4 = "203.0.113.7".parse().unwrap();
+        let v6 = [
+            "2001:db8::1".parse().unwrap(),
+            "2001:db8::2".parse().unwrap(),
+        ];
+        assert_eq!(
+            MyIp::new(Some(&v4), Some(&v6)).to_string(),
+            "203.0.113.7,2001:db8::1,2001:db8::2"
+        );
+        assert_eq!(MyIp::new(None, Some(&v6[..1])).to_string(), "2001:db8::1");
+        assert_eq!(MyIp::default().to_string(), "");
+    }
+}
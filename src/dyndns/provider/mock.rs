@@ -0,0 +1,125 @@
+//! A local HTTP server standing in for provider APIs in tests.
+
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    Router,
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, Method, StatusCode, Uri, header::CONTENT_TYPE},
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+use tokio::net::TcpListener;
+
+/// A request the server received.
+pub struct Received {
+    pub method: Method,
+    /// Path and query.
+    pub uri: String,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl Received {
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap()
+    }
+}
+
+/// The canned response to one method and path and query.
+pub struct Reply {
+    method: Method,
+    uri: String,
+    status: StatusCode,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Reply {
+    pub fn json(method: Method, uri: &str, body: Value) -> Self {
+        Self {
+            method,
+            uri: uri.to_owned(),
+            status: StatusCode::OK,
+            content_type: "application/json",
+            body: body.to_string(),
+        }
+    }
+
+    pub fn text(method: Method, uri: &str, body: &str) -> Self {
+        Self {
+            method,
+            uri: uri.to_owned(),
+            status: StatusCode::OK,
+            content_type: "text/plain",
+            body: body.to_owned(),
+        }
+    }
+
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+type Shared = (Arc<Vec<Reply>>, Arc<Mutex<Vec<Received>>>);
+
+pub struct MockServer {
+    pub url: String,
+    received: Arc<Mutex<Vec<Received>>>,
+}
+
+impl MockServer {
+    /// Serves `replies` on a free port, anything else gets a 404.
+    pub async fn start(replies: Vec<Reply>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(vec![]));
+        let app = Router::new()
+            .fallback(handle)
+            .with_state((Arc::new(replies), received.clone()));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        Self { url, received }
+    }
+
+    /// The requests received so far, in order.
+    pub fn received(&self) -> Vec<Received> {
+        std::mem::take(&mut *self.received.lock().unwrap())
+    }
+}
+
+async fn handle(
+    State((replies, received)): State<Shared>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let uri = uri
+        .path_and_query()
+        .map_or_else(|| uri.path().to_owned(), |path| path.as_str().to_owned());
+    let reply = replies
+        .iter()
+        .find(|reply| reply.method == method && reply.uri == uri);
+    let response = match reply {
+        Some(reply) => (
+            reply.status,
+            [(CONTENT_TYPE, reply.content_type)],
+            reply.body.clone(),
+        )
+            .into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            format!("no reply for {method} {uri}"),
+        )
+            .into_response(),
+    };
+    received.lock().unwrap().push(Received {
+        method,
+        uri,
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    });
+    response
+}
@@ -0,0 +1,139 @@
+mod cloudflare;
+mod duckdns;
+mod dyndns2;
+#[cfg(test)]
+pub(super) mod mock;
+mod rfc2136;
+mod webhook;
+
+use crate::{
+    Error,
+    db::{AttemptResult, DynDNS, ProviderKind},
+};
+
+use super::{
+    checker::{ipv4::Ipv4CheckResult, ipv6::Ipv6CheckResult},
+    http_client::HttpClient,
+};
+
+pub use cloudflare::CloudflareProvider;
+pub use duckdns::DuckDnsProvider;
+pub use dyndns2::{DynDnsAuth, Dyndns2Provider};
+pub use rfc2136::Rfc2136Provider;
+pub use webhook::WebhookProvider;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateOutcome {
+    Updated,
+    /// The provider already had the address on record.
+    Unchanged,
+    /// Transient failure, retried on the next cycle.
+    Failed(String),
+    /// The provider asked clients to back off before retrying.
+    RetryLater(String),
+    /// Retrying cannot succeed until the configuration changes.
+    Fatal(String),
+}
+
+impl UpdateOutcome {
+    /// Splits the outcome into the recorded result and the provider's reason.
+    pub fn into_attempt(self) -> (AttemptResult, Option<String>) {
+        match self {
+            Self::Updated => (AttemptResult::Updated, None),
+            Self::Unchanged => (AttemptResult::Unchanged, None),
+            Self::Failed(reason) => (AttemptResult::Failed, Some(reason)),
+            Self::RetryLater(reason) => (AttemptResult::RetryLater, Some(reason)),
+            Self::Fatal(reason) => (AttemptResult::Fatal, Some(reason)),
+        }
+    }
+
+    /// Orders outcomes so that the most severe one wins when combining several.
+    pub fn severity(&self) -> u8 {
+        match self {
+            Self::Unchanged => 0,
+            Self::Updated => 1,
+            Self::Failed(_) => 2,
+            Self::RetryLater(_) => 3,
+            Self::Fatal(_) => 4,
+        }
+    }
+}
+
+pub trait DnsProvider: Send + Sync {
+    async fn update(
+        &self,
+        ipv4: &Ipv4CheckResult,
+        ipv6: &Ipv6CheckResult,
+    ) -> Result<UpdateOutcome, Error>;
+
+    /// Checks the credentials and hostnames without changing any record.
+    /// Returns `None` when the protocol has no read-only call.
+    async fn probe(&self) -> Result<Option<String>, Error> {
+        Ok(None)
+    }
+
+    /// Removes the published addresses. Returns `None` when the provider
+    /// offers no such operation.
+    async fn clear(&self) -> Result<Option<String>, Error> {
+        Ok(None)
+    }
+}
+
+pub enum Provider<'a> {
+    Dyndns2(Dyndns2Provider<'a>),
+    Cloudflare(CloudflareProvider<'a>),
+    Rfc2136(Rfc2136Provider<'a>),
+    Webhook(WebhookProvider<'a>),
+    DuckDns(DuckDnsProvider<'a>),
+}
+
+impl<'a> Provider<'a> {
+    pub fn new(client: &'a HttpClient, config: &'a DynDNS) -> Result<Self, Error> {
+        let provider = match config.provider {
+            ProviderKind::Dyndns2 => {
+                Self::Dyndns2(Dyndns2Provider::new(client, DynDnsAuth::from(config)))
+            }
+            ProviderKind::Cloudflare => Self::Cloudflare(CloudflareProvider::new(client, config)),
+            ProviderKind::Rfc2136 => Self::Rfc2136(Rfc2136Provider::new(config, client.trace())?),
+            ProviderKind::Webhook => Self::Webhook(WebhookProvider::new(client, config)?),
+            ProviderKind::Duckdns => Self::DuckDns(DuckDnsProvider::new(client, config)),
+        };
+        Ok(provider)
+    }
+}
+
+impl<'a> DnsProvider for Provider<'a> {
+    async fn update(
+        &self,
+        ipv4: &Ipv4CheckResult,
+        ipv6: &Ipv6CheckResult,
+    ) -> Result<UpdateOutcome, Error> {
+        match self {
+            Provider::Dyndns2(provider) => provider.update(ipv4, ipv6).await,
+            Provider::Cloudflare(provider) => provider.update(ipv4, ipv6).await,
+            Provider::Rfc2136(provider) => provider.update(ipv4, ipv6).await,
+            Provider::Webhook(provider) => provider.update(ipv4, ipv6).await,
+            Provider::DuckDns(provider) => provider.update(ipv4, ipv6).await,
+        }
+    }
+
+    async fn probe(&self) -> Result<Option<String>, Error> {
+        match self {
+            Provider::Dyndns2(provider) => provider.probe().await,
+            Provider::Cloudflare(provider) => provider.probe().await,
+            Provider::Rfc2136(provider) => provider.probe().await,
+            Provider::Webhook(provider) => provider.probe().await,
+            Provider::DuckDns(provider) => provider.probe().await,
+        }
+    }
+
+    async fn clear(&self) -> Result<Option<String>, Error> {
+        match self {
+            Provider::Dyndns2(provider) => provider.clear().await,
+            Provider::Cloudflare(provider) => provider.clear().await,
+            Provider::Rfc2136(provider) => provider.clear().await,
+            Provider::Webhook(provider) => provider.clear().await,
+            Provider::DuckDns(provider) => provider.clear().await,
+        }
+    }
+}
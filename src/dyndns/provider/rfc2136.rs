@@ -0,0 +1,506 @@
+use std::net::{IpAddr, SocketAddr};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use rand_core::{OsRng, RngCore};
+
+use crate::{Error, db::DynDNS, error::ProviderError};
+
+use super::super::{
+    checker::{ipv4::Ipv4CheckResult, ipv6::Ipv6CheckResult},
+    dns::{
+        TsigKey, exchange,
+        message::{
+            CLASS_ANY, CLASS_IN, Header, MessageBuilder, OPCODE_QUERY, OPCODE_UPDATE, TYPE_A,
+            TYPE_AAAA, TYPE_SOA, answers, rcode_name,
+        },
+        resolve_server,
+    },
+    trace::{Trace, TraceEntry},
+};
+use super::{DnsProvider, UpdateOutcome};
+
+const PROVIDER: &str = "rfc2136";
+const DEFAULT_TTL: u32 = 300;
+
+const RCODE_NOERROR: u16 = 0;
+const RCODE_NXDOMAIN: u16 = 3;
+const RCODE_REFUSED: u16 = 5;
+const RCODE_NOTAUTH: u16 = 9;
+const RCODE_NOTZONE: u16 = 10;
+
+pub struct Rfc2136Provider<'a> {
+    server: &'a str,
+    config: &'a DynDNS,
+    ttl: u32,
+    key: TsigKey,
+    trace: Option<Trace>,
+}
+
+impl<'a> Rfc2136Provider<'a> {
+    pub fn new(config: &'a DynDNS, trace: Option<&Trace>) -> Result<Self, Error> {
+        let secret = STANDARD
+            .decode(&config.password)
+            .map_err(|err| Error::validation_failed(format!("invalid tsig secret: {err}")))?;
+        Ok(Self {
+            server: config.server.as_str(),
+            config,
+            ttl: config.options.ttl.unwrap_or(DEFAULT_TTL),
+            key: TsigKey::new(
+                config.username.as_str(),
+                config.options.tsig_algorithm.unwrap_or_default(),
+                secret,
+            ),
+            trace: trace.cloned(),
+        })
+    }
+
+    fn build(
+        &self,
+        zone: &str,
+        hostname: &str,
+        addresses: &[IpAddr],
+        rtypes: &[u16],
+    ) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        let mut builder = MessageBuilder::new(OsRng.next_u32() as u16, OPCODE_UPDATE);
+        builder.question(zone, TYPE_SOA, CLASS_IN)?;
+
+        for &rtype in rtypes {
+            builder.update(hostname, rtype, CLASS_ANY, 0, &[])?;
+        }
+        for address in addresses {
+            match address {
+                IpAddr::V4(ip) => {
+                    builder.update(hostname, TYPE_A, CLASS_IN, self.ttl, &ip.octets())?
+                }
+                IpAddr::V6(ip) => {
+                    builder.update(hostname, TYPE_AAAA, CLASS_IN, self.ttl, &ip.octets())?
+                }
+            };
+        }
+
+        let mut message = builder.finish();
+        let mac = self.key.sign(&mut message)?;
+        Ok((message, mac))
+    }
+}
+
+impl<'a> DnsProvider for Rfc2136Provider<'a> {
+    async fn update(
+        &self,
+        ipv4: &Ipv4CheckResult,
+        ipv6: &Ipv6CheckResult,
+    ) -> Result<UpdateOutcome, Error> {
+        let mut addresses = vec![];
+        let mut rtypes = vec![];
+
+        if let Some(address) = ipv4.external {
+            rtypes.push(TYPE_A);
+            addresses.push(IpAddr::V4(address));
+        }
+        if let Some(external) = ipv6.external.as_ref() {
+            rtypes.push(TYPE_AAAA);
+            addresses.extend(external.iter().copied().map(IpAddr::V6));
+        }
+        if addresses.is_empty() {
+            return Ok(UpdateOutcome::Unchanged);
+        }
+
+        let server = resolve_server(self.server).await?;
+        let mut outcome = UpdateOutcome::Unchanged;
+        for hostname in self.config.hostnames() {
+            let result = match self.zone(server, hostname).await? {
+                Ok(zone) => {
+                    self.send(server, &zone, hostname, &addresses, &rtypes)
+                        .await?
+                }
+                Err(reason) => UpdateOutcome::Fatal(reason),
+            };
+            if result.severity() > outcome.severity() {
+                outcome = result;
+            }
+        }
+        Ok(outcome)
+    }
+
+    /// Finds the zone of every hostname with signed SOA queries, which fails
+    /// when the server does not know the key or is not authoritative.
+    async fn probe(&self) -> Result<Option<String>, Error> {
+        let server = resolve_server(self.server).await?;
+        let mut zones: Vec<String> = vec![];
+        for hostname in self.config.hostnames() {
+            let zone = self
+                .zone(server, hostname)
+                .await?
+                .map_err(|reason| Error::provider_api(PROVIDER, reason))?;
+            if !zones.contains(&zone) {
+                zones.push(zone);
+            }
+        }
+
+        // A configured zone is taken as is by `zone`, so check it serves it.
+        if let Some(zone) = self.config.options.zone.as_deref() {
+            match self.query_soa(server, zone).await? {
+                Soa::Apex => {}
+                Soa::NotApex => {
+                    return Err(Error::provider_api(
+                        PROVIDER,
+                        format!("{server} has no SOA record for {zone}"),
+                    ));
+                }
+                Soa::Rejected(reason) => return Err(Error::provider_api(PROVIDER, reason)),
+            }
+        }
+        Ok(Some(format!("{} serves {}", server, zones.join(", "))))
+    }
+}
+
+/// What a signed SOA query for a name found out.
+enum Soa {
+    /// The name is the apex of a zone on the server.
+    Apex,
+    NotApex,
+    /// The server refused the query or the key, which retrying will not fix.
+    Rejected(String),
+}
+
+impl<'a> Rfc2136Provider<'a> {
+    /// The configured zone, or the closest enclosing zone the server has an
+    /// SOA record for. `Ok(Err(_))` carries the reason the server rejected
+    /// the lookup.
+    async fn zone(
+        &self,
+        server: SocketAddr,
+        hostname: &str,
+    ) -> Result<Result<String, String>, Error> {
+        if let Some(zone) = self.config.options.zone.as_ref() {
+            return Ok(Ok(zone.clone()));
+        }
+
+        let hostname = hostname.trim_end_matches('.');
+        let candidates = std::iter::once(hostname).chain(
+            hostname
+                .match_indices('.')
+                .map(|(index, _)| &hostname[index + 1..])
+                .filter(|zone| zone.contains('.')),
+        );
+        for candidate in candidates {
+            match self.query_soa(server, candidate).await? {
+                Soa::Apex => {
+                    debug!("rfc2136 zone of {} is {}", hostname, candidate);
+                    return Ok(Ok(candidate.to_owned()));
+                }
+                Soa::NotApex => {}
+                Soa::Rejected(reason) => return Ok(Err(reason)),
+            }
+        }
+
+        Err(ProviderError::ZoneNotFound {
+            provider: PROVIDER,
+            hostname: hostname.to_owned(),
+        }
+        .into())
+    }
+
+    async fn query_soa(&self, server: SocketAddr, name: &str) -> Result<Soa, Error> {
+        let mut builder = MessageBuilder::new(OsRng.next_u32() as u16, OPCODE_QUERY);
+        builder.question(name, TYPE_SOA, CLASS_IN)?;
+        let mut message = builder.finish();
+        let mac = self.key.sign(&mut message)?;
+
+        let response = self
+            .exchange(server, format!("query {name} SOA"), &message)
+            .await?;
+        let header = Header::parse(&response)?;
+        if !header.is_response() || header.id.to_be_bytes() != message[..2] {
+            return Err(Error::provider_invalid_response(
+                PROVIDER,
+                "reply is not a dns response",
+            ));
+        }
+        match self.key.verify(&response, &mac) {
+            Ok(()) => {}
+            Err(err) if err.is_fatal() => return Ok(Soa::Rejected(format!("{err} for {name}"))),
+            Err(err) => {
+                return Err(Error::provider_invalid_response(
+                    PROVIDER,
+                    format!("{err} for {name}"),
+                ));
+            }
+        }
+        match header.rcode() {
+            RCODE_NOERROR
+                if answers(&response)?
+                    .iter()
+                    .any(|record| record.rtype == TYPE_SOA) =>
+            {
+                Ok(Soa::Apex)
+            }
+            RCODE_NOERROR | RCODE_NXDOMAIN => Ok(Soa::NotApex),
+            rcode @ (RCODE_REFUSED | RCODE_NOTAUTH | RCODE_NOTZONE) => Ok(Soa::Rejected(format!(
+                "server responded {} for {}",
+                rcode_name(rcode),
+                name
+            ))),
+            rcode => Err(Error::provider_api(
+                PROVIDER,
+                format!("server responded {} for {}", rcode_name(rcode), name),
+            )),
+        }
+    }
+
+    async fn send(
+        &self,
+        server: SocketAddr,
+        zone: &str,
+        hostname: &str,
+        addresses: &[IpAddr],
+        rtypes: &[u16],
+    ) -> Result<UpdateOutcome, Error> {
+        let (message, mac) = self.build(zone, hostname, addresses, rtypes)?;
+        debug!("sending rfc2136 update for {} to {}", hostname, server);
+        let addresses = addresses
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        let response = self
+            .exchange(
+                server,
+                format!("update {} in {} to {}", hostname, zone, addresses),
+                &message,
+            )
+            .await?;
+
+        let header = Header::parse(&response)?;
+        if !header.is_response() || header.id.to_be_bytes() != message[..2] {
+            return Err(Error::provider_invalid_response(
+                PROVIDER,
+                "reply is not a dns response",
+            ));
+        }
+        match self.key.verify(&response, &mac) {
+            Ok(()) => {}
+            Err(err) if err.is_fatal() => {
+                return Ok(UpdateOutcome::Fatal(format!("{err} for {hostname}")));
+            }
+            Err(err) => {
+                return Err(Error::provider_invalid_response(
+                    PROVIDER,
+                    format!("{err} for {hostname}"),
+                ));
+            }
+        }
+        match header.rcode() {
+            RCODE_NOERROR => Ok(UpdateOutcome::Updated),
+            rcode @ (RCODE_REFUSED | RCODE_NOTAUTH | RCODE_NOTZONE) => Ok(UpdateOutcome::Fatal(
+                format!("server responded {} for {}", rcode_name(rcode), hostname),
+            )),
+            rcode => Err(Error::provider_api(
+                PROVIDER,
+                format!("server responded {} for {}", rcode_name(rcode), hostname),
+            )),
+        }
+    }
+
+    async fn exchange(
+        &self,
+        server: SocketAddr,
+        request: String,
+        message: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let result = exchange(server, None, message).await;
+        if let Some(trace) = self.trace.as_ref() {
+            let (response, error) = match &result {
+                Ok(response) => (
+                    Some(match Header::parse(response) {
+                        Ok(header) => rcode_name(header.rcode()).to_string(),
+                        Err(err) => err.to_string(),
+                    }),
+                    None,
+                ),
+                Err(err) => (None, Some(err.to_string())),
+            };
+            trace.record(TraceEntry::Dns {
+                server: server.to_string(),
+                request,
+                response,
+                error,
+            });
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::json;
+    use tokio::net::UdpSocket;
+
+    use crate::db::TsigAlgorithm;
+
+    use super::super::super::dns::message::{HEADER_LEN, read_name};
+    use super::*;
+
+    const SECRET: &[u8] = b"secret";
+
+    fn config(server: SocketAddr, hostname: &str) -> DynDNS {
+        serde_json::from_value(json!({
+            "server": server.to_string(),
+            "username": "update-key",
+            "password": STANDARD.encode(SECRET),
+            "hostname": hostname,
+            "ip": 1,
+            "interface": "lo",
+            "provider": "rfc2136",
+            "options": { "ttl": 120 },
+        }))
+        .unwrap()
+    }
+
+    fn ipv4(address: &str) -> Ipv4CheckResult {
+        Ipv4CheckResult {
+            external: Some(address.parse().unwrap()),
+            ..Default::default()
+        }
+    }
+
+    /// An authoritative server for `example.com` that signs its responses and
+    /// keeps the updates it accepts.
+    async fn server() -> (SocketAddr, Arc<Mutex<Vec<Vec<u8>>>>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let updates = Arc::new(Mutex::new(vec![]));
+        let received = updates.clone();
+        let key = TsigKey::new("update-key", TsigAlgorithm::HmacSha256, SECRET.to_vec());
+        tokio::spawn(async move {
+            let mut buf = [0; 512];
+            loop {
+                let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+                let request = &buf[..len];
+                let name = read_name(request, HEADER_LEN).unwrap();
+                let question_end = HEADER_LEN + name.len() + 2 + 4;
+                let opcode = (request[2] >> 3) & 0x0f;
+
+                let mut response = request[..question_end].to_vec();
+                response[4..12].copy_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+                let rcode = if !name.ends_with("example.com") {
+                    RCODE_REFUSED
+                } else if opcode == OPCODE_UPDATE as u8 {
+                    received.lock().unwrap().push(request.to_vec());
+                    RCODE_NOERROR
+                } else {
+                    if name == "example.com" {
+                        response[6..8].copy_from_slice(&1u16.to_be_bytes());
+                        response.extend_from_slice(&[0xc0, HEADER_LEN as u8]);
+                        response.extend_from_slice(&TYPE_SOA.to_be_bytes());
+                        response.extend_from_slice(&CLASS_IN.to_be_bytes());
+                        response.extend_from_slice(&60u32.to_be_bytes());
+                        response.extend_from_slice(&24u16.to_be_bytes());
+                        response.extend_from_slice(&[0xc0, HEADER_LEN as u8]);
+                        response.extend_from_slice(&[0xc0, HEADER_LEN as u8]);
+                        response.extend_from_slice(&[0; 20]);
+                    }
+                    RCODE_NOERROR
+                };
+                let flags = 0x8400 | ((opcode as u16) << 11) | rcode;
+                response[2..4].copy_from_slice(&flags.to_be_bytes());
+                key.sign_response(&mut response, request).unwrap();
+                socket.send_to(&response, peer).await.unwrap();
+            }
+        });
+        (addr, updates)
+    }
+
+    #[tokio::test]
+    async fn discovers_zone_and_sends_signed_update() {
+        let (server, updates) = server().await;
+        let config = config(server, "host.example.com");
+        let provider = Rfc2136Provider::new(&config, None).unwrap();
+
+        let outcome = provider
+            .update(&ipv4("203.0.113.7"), &Default::default())
+            .await;
+        assert_eq!(outcome.unwrap(), UpdateOutcome::Updated);
+
+        let updates = updates.lock().unwrap();
+        assert_eq!(updates.len(), 1);
+        let update = &updates[0];
+        // One zone, no prerequisites, a delete and an add, and the TSIG record.
+        assert_eq!(&update[4..12], &[0, 1, 0, 0, 0, 2, 0, 1]);
+        assert_eq!(read_name(update, HEADER_LEN).unwrap(), "example.com");
+    }
+
+    #[tokio::test]
+    async fn probe_reports_discovered_zone() {
+        let (server, _) = server().await;
+        let config = config(server, "a.b.example.com, example.com");
+        let provider = Rfc2136Provider::new(&config, None).unwrap();
+
+        let detail = provider.probe().await.unwrap();
+        assert_eq!(detail.unwrap(), format!("{server} serves example.com"));
+    }
+
+    #[tokio::test]
+    async fn refused_zone_is_fatal() {
+        let (server, updates) = server().await;
+        let config = config(server, "host.example.org");
+        let provider = Rfc2136Provider::new(&config, None).unwrap();
+
+        let outcome = provider
+            .update(&ipv4("203.0.113.7"), &Default::default())
+            .await;
+        assert_eq!(
+            outcome.unwrap(),
+            UpdateOutcome::Fatal("server responded REFUSED for host.example.org".to_owned())
+        );
+        assert!(updates.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejects_response_signed_with_other_key() {
+        let (server, _) = server().await;
+        let mut config = config(server, "host.example.com");
+        config.options.zone = Some("example.com".to_owned());
+        config.password = STANDARD.encode(b"other");
+        let provider = Rfc2136Provider::new(&config, None).unwrap();
+
+        let err = provider
+            .update(&ipv4("203.0.113.7"), &Default::default())
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("response signature does not verify for host.example.com"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn builds_update_replacing_the_rrset() {
+        let config = config("127.0.0.1:53".parse().unwrap(), "host.example.com");
+        let provider = Rfc2136Provider::new(&config, None).unwrap();
+        let (message, mac) = provider
+            .build(
+                "example.com",
+                "host.example.com",
+                &["203.0.113.7".parse().unwrap()],
+                &[TYPE_A],
+            )
+            .unwrap();
+        assert_eq!(mac.len(), 32);
+
+        // Zone section: example.com SOA IN.
+        let mut offset = HEADER_LEN + 13;
+        assert_eq!(&message[offset..offset + 4], &[0, 6, 0, 1]);
+        offset += 4;
+
+        let mut expected = b"\x04host\x07example\x03com\x00".to_vec();
+        // Delete the A RRset: class ANY, ttl 0, no data.
+        expected.extend_from_slice(&[0, 1, 0, 255, 0, 0, 0, 0, 0, 0]);
+        expected.extend_from_slice(b"\x04host\x07example\x03com\x00");
+        expected.extend_from_slice(&[0, 1, 0, 1, 0, 0, 0, 120, 0, 4, 203, 0, 113, 7]);
+        assert_eq!(&message[offset..offset + expected.len()], expected);
+    }
+}
@@ -0,0 +1,368 @@
+use isahc::{
+    Request,
+    http::{Method, StatusCode},
+    prelude::AsyncReadResponseExt,
+};
+use regex::Regex;
+
+use crate::{
+    Error,
+    db::{DynDNS, WebhookOptions},
+    util::percent_encode,
+};
+
+use super::super::{
+    checker::{ipv4::Ipv4CheckResult, ipv6::Ipv6CheckResult},
+    http_client::HttpClient,
+};
+use super::{DnsProvider, UpdateOutcome};
+
+const MAX_REASON_LEN: usize = 200;
+
+/// Sends a request built from templates once per hostname.
+///
+/// Templates may use `{hostname}`, `{server}`, `{username}`, `{password}`,
+/// `{ipv4}`, `{ipv6}` (the first address), `{ipv6_all}` (comma separated),
+/// `{old_ipv4}` and `{old_ipv6}`. Values are percent-encoded in the url and
+/// inserted as is elsewhere; addresses that are not being updated are empty.
+/// Braces around anything else are kept, so JSON bodies need no escaping.
+pub struct WebhookProvider<'a> {
+    client: &'a HttpClient,
+    config: &'a DynDNS,
+    options: &'a WebhookOptions,
+    success_body: Option<Regex>,
+}
+
+impl<'a> WebhookProvider<'a> {
+    pub fn new(client: &'a HttpClient, config: &'a DynDNS) -> Result<Self, Error> {
+        let options = config
+            .options
+            .webhook
+            .as_ref()
+            .ok_or_else(|| Error::validation_failed("webhook requires options.webhook"))?;
+        let success_body = options
+            .success
+            .body
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|err| Error::validation_failed(format!("invalid webhook regex: {err}")))?;
+        Ok(Self {
+            client,
+            config,
+            options,
+            success_body,
+        })
+    }
+
+    async fn send(&self, variables: &Variables) -> Result<UpdateOutcome, Error> {
+        let method = variables.render(&self.options.method, false);
+        let Ok(method) = Method::from_bytes(method.to_uppercase().as_bytes()) else {
+            return Ok(UpdateOutcome::Fatal(format!(
+                "invalid webhook method {method}"
+            )));
+        };
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(variables.render(&self.options.url, true));
+        for (name, value) in &self.options.headers {
+            builder = builder.header(name, variables.render(value, false));
+        }
+        let body = self
+            .options
+            .body
+            .as_deref()
+            .map(|body| variables.render(body, false).into_bytes())
+            .unwrap_or_default();
+        let request = match builder.body(body) {
+            Ok(request) => request,
+            Err(err) => {
+                return Ok(UpdateOutcome::Fatal(format!(
+                    "invalid webhook request: {err}"
+                )));
+            }
+        };
+
+        let mut response = self.client.send_async(request).await?;
+        let status = response.status();
+        let body = response.text().await?;
+        let message = body.trim();
+        debug!("code: {status}, msg: {message}");
+
+        let status_ok = if self.options.success.status.is_empty() {
+            status.is_success()
+        } else {
+            self.options.success.status.contains(&status.as_u16())
+        };
+        let body_ok = self
+            .success_body
+            .as_ref()
+            .is_none_or(|regex| regex.is_match(&body));
+        if status_ok && body_ok {
+            return Ok(UpdateOutcome::Updated);
+        }
+
+        let reason = format!(
+            "code: {status}, {}",
+            message.chars().take(MAX_REASON_LEN).collect::<String>()
+        );
+        Ok(match status {
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => {
+                UpdateOutcome::RetryLater(reason)
+            }
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => UpdateOutcome::Fatal(reason),
+            _ => UpdateOutcome::Failed(reason),
+        })
+    }
+}
+
+impl<'a> DnsProvider for WebhookProvider<'a> {
+    async fn update(
+        &self,
+        ipv4: &Ipv4CheckResult,
+        ipv6: &Ipv6CheckResult,
+    ) -> Result<UpdateOutcome, Error> {
+        let mut outcome = UpdateOutcome::Unchanged;
+        for hostname in self.config.hostnames() {
+            let variables = Variables::new(self.config, hostname, ipv4, ipv6);
+            let result = self.send(&variables).await?;
+            if result.severity() > outcome.severity() {
+                outcome = result;
+            }
+        }
+        Ok(outcome)
+    }
+}
+
+struct Variables(Vec<(&'static str, String)>);
+
+impl Variables {
+    fn new(
+        config: &DynDNS,
+        hostname: &str,
+        ipv4: &Ipv4CheckResult,
+        ipv6: &Ipv6CheckResult,
+    ) -> Self {
+        let join = |addresses: Option<&Vec<_>>| {
+            addresses
+                .map(|addresses| {
+                    addresses
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(",")
+                })
+                .unwrap_or_default()
+        };
+        let ipv6_first = ipv6
+            .external
+            .as_ref()
+            .and_then(|addresses| addresses.first())
+            .map(ToString::to_string)
+            .unwrap_or_default();
+
+        Self(vec![
+            ("hostname", hostname.to_string()),
+            ("server", config.server.clone()),
+            ("username", config.username.clone()),
+            ("password", config.password.clone()),
+            (
+                "ipv4",
+                ipv4.external.map(|ip| ip.to_string()).unwrap_or_default(),
+            ),
+            ("ipv6", ipv6_first),
+            ("ipv6_all", join(ipv6.external.as_ref())),
+            (
+                "old_ipv4",
+                ipv4.previous.map(|ip| ip.to_string()).unwrap_or_default(),
+            ),
+            ("old_ipv6", join(ipv6.previous.as_ref())),
+        ])
+    }
+
+    /// Replaces every `{name}` of a known variable, percent-encoding the
+    /// values when `encode` is set.
+    fn render(&self, template: &str, encode: bool) -> String {
+        let mut rendered = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let value = rest[1..].find('}').and_then(|end| {
+                let name = &rest[1..end + 1];
+                self.0
+                    .iter()
+                    .find(|(variable, _)| *variable == name)
+                    .map(|(_, value)| (value, end + 2))
+            });
+            match value {
+                Some((value, len)) => {
+                    if encode {
+                        rendered.push_str(&percent_encode(value));
+                    } else {
+                        rendered.push_str(value);
+                    }
+                    rest = &rest[len..];
+                }
+                None => {
+                    rendered.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::http::{Method as ServerMethod, StatusCode as ServerStatus};
+    use serde_json::{Value, json};
+
+    use super::super::mock::{MockServer, Reply};
+    use super::*;
+
+    fn config(webhook: Value) -> DynDNS {
+        serde_json::from_value(json!({
+            "server": "api.example.com",
+            "username": "user",
+            "password": "p@ss word",
+            "hostname": "home.example.com",
+            "ip": 3,
+            "interface": "lo",
+            "provider": "webhook",
+            "options": { "webhook": webhook },
+        }))
+        .unwrap()
+    }
+
+    fn variables() -> Variables {
+        let config = config(json!({ "url": "https://api.example.com" }));
+        let ipv4 = Ipv4CheckResult {
+            previous: Some("198.51.100.1".parse().unwrap()),
+            external: Some("203.0.113.7".parse().unwrap()),
+            ..Default::default()
+        };
+        let ipv6 = Ipv6CheckResult {
+            external: Some(vec![
+                "2001:db8::1".parse().unwrap(),
+                "2001:db8::2".parse().unwrap(),
+            ]),
+            ..Default::default()
+        };
+        Variables::new(&config, "home.example.com", &ipv4, &ipv6)
+    }
+
+    #[test]
+    fn renders_known_variables() {
+        let variables = variables();
+        assert_eq!(
+            variables.render("{hostname} {ipv4} {old_ipv4} {ipv6} {ipv6_all}", false),
+            "home.example.com 203.0.113.7 198.51.100.1 2001:db8::1 2001:db8::1,2001:db8::2"
+        );
+        // Nothing to publish or nothing published before renders empty.
+        assert_eq!(variables.render("[{old_ipv6}]", false), "[]");
+    }
+
+    #[test]
+    fn percent_encodes_values_in_url_only() {
+        let variables = variables();
+        assert_eq!(
+            variables.render(
+                "https://{server}/u?user={username}&pass={password}&ip={ipv6_all}",
+                true
+            ),
+            "https://api.example.com/u?user=user&pass=p%40ss%20word&ip=2001%3Adb8%3A%3A1%2C2001%3Adb8%3A%3A2"
+        );
+        assert_eq!(variables.render("{password}", false), "p@ss word");
+    }
+
+    #[test]
+    fn keeps_other_braces() {
+        let variables = variables();
+        assert_eq!(
+            variables.render(r#"{"name": "{hostname}", "content": "{ipv4}"}"#, false),
+            r#"{"name": "home.example.com", "content": "203.0.113.7"}"#
+        );
+        assert_eq!(
+            variables.render("{{hostname}} {unknown} {ipv4", false),
+            "{home.example.com} {unknown} {ipv4"
+        );
+        assert_eq!(variables.render("{ü}{ipv4}", false), "{ü}203.0.113.7");
+    }
+
+    async fn send(reply: Reply, success: Value) -> UpdateOutcome {
+        let server = MockServer::start(vec![reply]).await;
+        let config = config(json!({
+            "method": "post",
+            "url": format!("{}/update?ip={{ipv4}}", server.url),
+            "headers": { "authorization": "Bearer {password}" },
+            "body": r#"{"host": "{hostname}"}"#,
+            "success": success,
+        }));
+        let client = HttpClient::new(1, Duration::ZERO);
+        let ipv4 = Ipv4CheckResult {
+            external: Some("203.0.113.7".parse().unwrap()),
+            ..Default::default()
+        };
+        let outcome = WebhookProvider::new(&client, &config)
+            .unwrap()
+            .update(&ipv4, &Default::default())
+            .await
+            .unwrap();
+
+        let received = server.received();
+        assert_eq!(received[0].method, ServerMethod::POST);
+        assert_eq!(received[0].headers["authorization"], "Bearer p@ss word");
+        assert_eq!(received[0].json(), json!({ "host": "home.example.com" }));
+        outcome
+    }
+
+    #[tokio::test]
+    async fn checks_status_and_body() {
+        let reply = || Reply::text(ServerMethod::POST, "/update?ip=203.0.113.7", "status: ok");
+        assert_eq!(send(reply(), json!({})).await, UpdateOutcome::Updated);
+        assert_eq!(
+            send(
+                reply().status(ServerStatus::ACCEPTED),
+                json!({ "status": [202] })
+            )
+            .await,
+            UpdateOutcome::Updated
+        );
+        assert_eq!(
+            send(reply(), json!({ "status": [204] })).await,
+            UpdateOutcome::Failed("code: 200 OK, status: ok".to_string())
+        );
+        assert_eq!(
+            send(reply(), json!({ "body": "^status: (ok|nochg)$" })).await,
+            UpdateOutcome::Updated
+        );
+        assert_eq!(
+            send(reply(), json!({ "body": "^good" })).await,
+            UpdateOutcome::Failed("code: 200 OK, status: ok".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn maps_failure_status() {
+        let reply = |status| {
+            Reply::text(ServerMethod::POST, "/update?ip=203.0.113.7", "nope").status(status)
+        };
+        assert_eq!(
+            send(reply(ServerStatus::TOO_MANY_REQUESTS), json!({})).await,
+            UpdateOutcome::RetryLater("code: 429 Too Many Requests, nope".to_string())
+        );
+        assert_eq!(
+            send(reply(ServerStatus::FORBIDDEN), json!({})).await,
+            UpdateOutcome::Fatal("code: 403 Forbidden, nope".to_string())
+        );
+        assert_eq!(
+            send(reply(ServerStatus::INTERNAL_SERVER_ERROR), json!({})).await,
+            UpdateOutcome::Failed("code: 500 Internal Server Error, nope".to_string())
+        );
+    }
+}
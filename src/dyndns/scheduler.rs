@@ -1,66 +1,246 @@
-use std::time::Duration;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
 
-use tokio::{sync::watch, time};
+use chrono::{NaiveDateTime, Utc};
+use rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use tokio::{
+    sync::{mpsc, oneshot, watch},
+    time,
+};
 
 use crate::{
     DbPool, Error,
-    db::{DynDNS, History, IpVersion},
+    db::{
+        AttemptResult, DynDNS, DynDnsAttempt, DynDnsRecord, DynDnsStatus, Event, EventLevel,
+        History, HistoryEvent, IpVersion, Ipv4Reachability, RecordLookup, Settings,
+    },
 };
 
 use super::{
     checker::{
         ipv4::{Ipv4CheckResult, Ipv4Checker},
-        ipv6::{Ipv6CheckResult, Ipv6Checker, Ipv6HistorySnapshot, parse_ipv6_list},
+        ipv6::{Ipv6CheckResult, Ipv6Checker, Ipv6HistorySnapshot, Ipv6Suffix, parse_ipv6_list},
         run_checker,
     },
+    dns::message::{TYPE_A, TYPE_AAAA},
     http_client::HttpClient,
-    updater::{DynDnsAuth, DynDnsUpdater},
+    lookup::{ExternalIpLookup, LookupAnswer, interface_index, watch_addresses},
+    provider::UpdateOutcome,
+    updater::DynDnsUpdater,
+    verify::{DnsVerifier, published_addresses},
 };
 
+const RETRY_LATER_DELAY: Duration = Duration::from_secs(30 * 60);
+/// Delay after the first failed attempt, doubled for every further failure.
+const BACKOFF_BASE: Duration = Duration::from_secs(30);
+/// Upper bound for the backoff, also the cool-down of an open circuit breaker.
+const BACKOFF_MAX: Duration = Duration::from_secs(60 * 60);
+/// Quiet period after an address event before the cycle runs, so that a
+/// renumbering or DAD burst results in a single check.
+const ADDRESS_EVENT_DEBOUNCE: Duration = Duration::from_secs(3);
+
 pub async fn launch(
     pool: DbPool,
     interval_rx: watch::Receiver<u64>,
+    trigger_rx: mpsc::Receiver<TriggerRequest>,
     shutdown_rx: watch::Receiver<bool>,
 ) {
     info!("DynDNS scheduler start");
-    let scheduler = DynDnsScheduler::new(pool, interval_rx, shutdown_rx).await;
+    let scheduler = DynDnsScheduler::new(pool, interval_rx, trigger_rx, shutdown_rx).await;
     scheduler.run().await;
     info!("DynDNS scheduler stop");
 }
 
+pub fn trigger_channel() -> (SchedulerHandle, mpsc::Receiver<TriggerRequest>) {
+    let (trigger_tx, trigger_rx) = mpsc::channel(8);
+    (SchedulerHandle { trigger_tx }, trigger_rx)
+}
+
+/// Wakes the scheduler from outside its own loop.
+#[derive(Clone)]
+pub struct SchedulerHandle {
+    trigger_tx: mpsc::Sender<TriggerRequest>,
+}
+
+impl SchedulerHandle {
+    /// Runs a cycle right away and waits for its report. `force` pushes the
+    /// detected addresses even when they match the last recorded update.
+    pub async fn trigger(&self, force: bool) -> Result<CycleReport, Error> {
+        let (reply, reply_rx) = oneshot::channel();
+        self.trigger_tx
+            .send(TriggerRequest { force, reply })
+            .await
+            .map_err(|_| Error::scheduler_unavailable())?;
+        reply_rx.await.map_err(|_| Error::scheduler_unavailable())?
+    }
+}
+
+pub struct TriggerRequest {
+    force: bool,
+    reply: oneshot::Sender<Result<CycleReport, Error>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CycleReport {
+    forced: bool,
+    targets: Vec<TargetReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TargetReport {
+    id: i32,
+    hostname: String,
+    state: TargetState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<AttemptResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum TargetState {
+    Paused,
+    BackingOff,
+    CircuitOpen,
+    NoChange,
+    Attempted,
+}
+
+impl TargetReport {
+    fn new(target: &DynDnsRecord, state: TargetState) -> Self {
+        Self {
+            id: target.id,
+            hostname: target.config.hostname.clone(),
+            state,
+            result: None,
+            response: None,
+        }
+    }
+}
+
+/// An update that failed, before or while calling the provider.
+struct TargetError {
+    error: Error,
+    /// Only failures of the provider call count towards the circuit breaker.
+    provider_called: bool,
+}
+
+impl TargetError {
+    fn provider(error: Error) -> Self {
+        Self {
+            error,
+            provider_called: true,
+        }
+    }
+}
+
+impl From<Error> for TargetError {
+    fn from(error: Error) -> Self {
+        Self {
+            error,
+            provider_called: false,
+        }
+    }
+}
+
 pub struct DynDnsScheduler {
     pool: DbPool,
     client: HttpClient,
     interval_rx: watch::Receiver<u64>,
+    trigger_rx: mpsc::Receiver<TriggerRequest>,
     shutdown_rx: watch::Receiver<bool>,
+    address_rx: mpsc::Receiver<i32>,
+    /// Indexes of the interfaces used by the targets of the last cycle.
+    watched: HashSet<i32>,
     interval_secs: u64,
+    retry_after: HashMap<i32, time::Instant>,
+    /// Consecutive failed attempts per target, cleared by a successful one.
+    failures: HashMap<i32, u32>,
 }
 
 impl DynDnsScheduler {
     async fn new(
         pool: DbPool,
         interval_rx: watch::Receiver<u64>,
+        trigger_rx: mpsc::Receiver<TriggerRequest>,
         shutdown_rx: watch::Receiver<bool>,
     ) -> Self {
         let client = HttpClient::new(3, Duration::from_millis(200));
         let interval_secs = Self::load_interval_seconds(&pool).await;
-        Self {
+        let address_rx = watch_addresses().unwrap_or_else(|err| {
+            warn!("address events unavailable, polling only: {}", err);
+            // The sender is dropped right away, so `recv` returns `None` and
+            // `wait` disables its address branch.
+            mpsc::channel(1).1
+        });
+        let mut scheduler = Self {
             pool,
             client,
             interval_rx,
+            trigger_rx,
             shutdown_rx,
+            address_rx,
+            watched: HashSet::new(),
             interval_secs,
+            retry_after: HashMap::new(),
+            failures: HashMap::new(),
+        };
+        if let Err(err) = scheduler.restore_failures().await {
+            error!("failed to restore backoff state: {}", err);
         }
+        scheduler
+    }
+
+    /// Picks the backoff and circuit breaker state recorded before a restart
+    /// back up, so a failing provider is not hit again right away.
+    async fn restore_failures(&mut self) -> Result<(), Error> {
+        let conn = self.pool.get().await?;
+        let threshold = Settings::get(&conn).await?.failure_threshold.max(1) as u32;
+        let now = Utc::now().naive_utc();
+        for status in DynDnsStatus::load_all(&conn).await? {
+            if status.consecutive_failures <= 0 {
+                continue;
+            }
+            let mut failures = status.consecutive_failures as u32;
+            let mut retry_at = status.next_run_at;
+            if let Some(opened_at) = status.circuit_opened_at {
+                failures = failures.max(threshold);
+                retry_at = retry_at.or(Some(opened_at + BACKOFF_MAX));
+            }
+            self.failures.insert(status.dyndns_id, failures);
+            if let Some(retry_at) = retry_at
+                && let Ok(remaining) = (retry_at - now).to_std()
+            {
+                self.retry_after
+                    .insert(status.dyndns_id, time::Instant::now() + remaining);
+            }
+        }
+        if !self.failures.is_empty() {
+            info!("restored backoff state of {} targets", self.failures.len());
+        }
+        Ok(())
     }
 
     async fn run(mut self) {
+        let mut trigger: Option<TriggerRequest> = None;
         loop {
             let mut interval = time::interval(Duration::from_secs(self.interval_secs));
             let start_time = interval.tick().await;
 
-            if let Err(err) = self.execute_cycle().await {
+            let force = trigger.as_ref().is_some_and(|request| request.force);
+            let result = self.execute_cycle(force).await;
+            if let Err(err) = &result {
                 error!("{}", err);
             }
+            if let Some(request) = trigger.take() {
+                // The caller may have gone away while the cycle was running.
+                let _ = request.reply.send(result);
+            }
 
             debug!("sleep {}s", self.interval_secs);
 
@@ -69,56 +249,450 @@ impl DynDnsScheduler {
                 _ = shutdown.changed() => {
                     return;
                 },
-                _ = self.wait(start_time, interval) => {
+                request = self.wait(start_time, interval) => {
                     debug!("wake");
+                    trigger = request;
                 }
             }
         }
     }
 
-    async fn execute_cycle(&self) -> Result<(), Error> {
-        let config = self.load_config().await?;
-        let ipv4_previous = self.load_ipv4_history().await?;
-        let ipv6_history = self.load_ipv6_history().await?;
+    async fn execute_cycle(&mut self, force: bool) -> Result<CycleReport, Error> {
+        let now = time::Instant::now();
+        self.retry_after.retain(|_, until| *until > now);
+
+        let mut report = CycleReport {
+            forced: force,
+            targets: vec![],
+        };
+        let settings = self.load_settings().await?;
+        let threshold = settings.failure_threshold.max(1) as u32;
+        let mut targets = vec![];
+        for target in self.load_targets().await? {
+            if let Some(pause) = target.paused() {
+                debug!(
+                    "[{}] updates paused since {}: {}",
+                    target.config.hostname, pause.paused_at, pause.reason
+                );
+                let mut entry = TargetReport::new(&target, TargetState::Paused);
+                entry.response = Some(pause.reason);
+                report.targets.push(entry);
+                continue;
+            }
+            // A forced cycle is an explicit request to push, so it goes
+            // through the backoff and an open breaker.
+            if !force && self.retry_after.contains_key(&target.id) {
+                let state = if self.failures.get(&target.id) >= Some(&threshold) {
+                    TargetState::CircuitOpen
+                } else {
+                    TargetState::BackingOff
+                };
+                debug!(
+                    "[{}] backing off ({:?}), skipping cycle",
+                    target.config.hostname, state
+                );
+                report.targets.push(TargetReport::new(&target, state));
+                continue;
+            }
+            targets.push(target);
+        }
+        self.watched = targets
+            .iter()
+            .filter_map(|target| interface_index(&target.config.interface).ok())
+            .collect();
+        if targets.is_empty() {
+            debug!("no dyndns targets to update");
+            return Ok(report);
+        }
+
+        let client = self.client.clone();
+        let mut families: BTreeMap<&str, (bool, bool)> = BTreeMap::new();
+        for target in &targets {
+            let (run_ipv4, run_ipv6) = families
+                .entry(target.config.interface.as_str())
+                .or_default();
+            *run_ipv4 |= matches!(target.config.ip, IpVersion::V4 | IpVersion::All);
+            *run_ipv6 |= matches!(target.config.ip, IpVersion::V6 | IpVersion::All);
+        }
+
+        let mut detections = HashMap::with_capacity(families.len());
+        for (interface, (run_ipv4, run_ipv6)) in families {
+            let detection = Detection::run(&client, interface, &settings, run_ipv4, run_ipv6).await;
+            detections.insert(interface, detection);
+        }
 
-        let client = &self.client;
-        let interface = config.interface.as_str();
-        let run_ipv4 = matches!(config.ip, IpVersion::V4 | IpVersion::ALL);
-        let run_ipv6 = matches!(config.ip, IpVersion::V6 | IpVersion::ALL);
+        for target in &targets {
+            let detection = &detections[target.config.interface.as_str()];
+            let next_run_at =
+                Some(Utc::now().naive_utc() + Duration::from_secs(self.interval_secs));
+            let mut attempt = match self.update_target(target, detection, force).await {
+                Ok(None) => None,
+                Ok(Some(outcome)) => Some(Self::attempt_for(outcome, next_run_at)),
+                Err(err) => {
+                    error!("[{}] {}", target.config.hostname, err.error);
+                    Some(DynDnsAttempt {
+                        result: AttemptResult::Error,
+                        response: Some(err.error.to_string()),
+                        next_run_at,
+                        circuit_open: false,
+                        provider_called: err.provider_called,
+                    })
+                }
+            };
+            if let Some(attempt) = attempt.as_mut()
+                && let Some(message) =
+                    self.track_failures(target.id, &target.config.hostname, attempt, threshold)
+            {
+                self.record_event(EventLevel::Warning, message).await;
+            }
 
-        let (ipv4_result, ipv6_result) = match (run_ipv4, run_ipv6) {
-            (true, true) => {
-                let ipv4_checker = Ipv4Checker::new(client, interface, ipv4_previous);
-                let ipv6_checker = Ipv6Checker::new(client, interface, ipv6_history);
-                tokio::join!(run_checker(ipv4_checker), run_checker(ipv6_checker))
+            let mut entry = match &attempt {
+                Some(attempt) => {
+                    let mut entry = TargetReport::new(target, TargetState::Attempted);
+                    entry.result = Some(attempt.result);
+                    entry.response = attempt.response.clone();
+                    entry
+                }
+                None => TargetReport::new(target, TargetState::NoChange),
+            };
+            let reachability = matches!(target.config.ip, IpVersion::V4 | IpVersion::All)
+                .then(|| detection.ipv4_checker.reachability())
+                .flatten();
+            if let Err(err) = self
+                .record_status(target.id, attempt, next_run_at, reachability)
+                .await
+            {
+                error!("[{}] {}", target.config.hostname, err);
+                entry.response.get_or_insert_with(|| err.to_string());
             }
-            (true, false) => {
-                let ipv4_checker = Ipv4Checker::new(client, interface, ipv4_previous);
-                (run_checker(ipv4_checker).await, Ipv6CheckResult::default())
+            report.targets.push(entry);
+        }
+
+        for detection in detections.values() {
+            for message in detection.take_warnings() {
+                self.record_event(EventLevel::Warning, message).await;
             }
-            (false, true) => {
-                let ipv6_checker = Ipv6Checker::new(client, interface, ipv6_history);
-                (Ipv4CheckResult::default(), run_checker(ipv6_checker).await)
+        }
+
+        Ok(report)
+    }
+
+    async fn update_target(
+        &self,
+        target: &DynDnsRecord,
+        detection: &Detection<'_>,
+        force: bool,
+    ) -> Result<Option<UpdateOutcome>, TargetError> {
+        let config = &target.config;
+        let run_ipv4 = matches!(config.ip, IpVersion::V4 | IpVersion::All);
+        let run_ipv6 = matches!(config.ip, IpVersion::V6 | IpVersion::All);
+
+        let ipv4_missing = run_ipv4 && detection.ipv4.is_none();
+        let ipv6_missing = run_ipv6 && detection.ipv6.is_none();
+        if (ipv4_missing || !run_ipv4) && (ipv6_missing || !run_ipv6) {
+            return Err(if ipv4_missing {
+                Error::ipv4_not_found()
+            } else {
+                Error::ipv6_not_found()
             }
-            (false, false) => (Ipv4CheckResult::default(), Ipv6CheckResult::default()),
+            .into());
+        }
+
+        let mut event = if force {
+            HistoryEvent::Forced
+        } else {
+            HistoryEvent::Change
         };
+        let (mut ipv4_result, mut ipv6_result) = self
+            .compare(target, detection, run_ipv4, run_ipv6, force)
+            .await?;
+        if ipv4_result.external.is_none()
+            && ipv6_result.external.is_none()
+            && self.refresh_due(target).await?
+        {
+            info!(
+                "[{}] last update is older than max age, refreshing",
+                config.hostname
+            );
+            (ipv4_result, ipv6_result) = self
+                .compare(target, detection, run_ipv4, run_ipv6, true)
+                .await?;
+            event = HistoryEvent::Refresh;
+        }
 
-        let auth = DynDnsAuth::from(&config);
-        let updater = DynDnsUpdater::new(&self.client, auth);
-        let updated = updater.apply(&ipv4_result, &ipv6_result).await?;
+        let updater = DynDnsUpdater::new(&self.client, config)?;
+        let Some(outcome) = updater
+            .apply(&ipv4_result, &ipv6_result)
+            .await
+            .map_err(TargetError::provider)?
+        else {
+            return Ok(None);
+        };
 
-        if updated {
-            self.persist_history(&ipv4_result, &ipv6_result).await?;
+        match &outcome {
+            UpdateOutcome::Updated | UpdateOutcome::Unchanged => {
+                let (ipv4_id, ipv6_id) = self
+                    .persist_history(target, &ipv4_result, &ipv6_result, event)
+                    .await?;
+                if config.verify.is_some() {
+                    let ipv4 = ipv4_id.zip(ipv4_result.external.map(|addr| vec![addr.into()]));
+                    let ipv6 = ipv6_id.zip(
+                        ipv6_result
+                            .external
+                            .map(|addrs| addrs.into_iter().map(IpAddr::from).collect()),
+                    );
+                    self.spawn_verification(target, ipv4, ipv6);
+                }
+            }
+            UpdateOutcome::Failed(_) | UpdateOutcome::RetryLater(_) => {}
+            UpdateOutcome::Fatal(reason) => {
+                error!(
+                    "[{}] pausing updates until the configuration changes",
+                    config.hostname
+                );
+                let conn = self.pool.get().await.map_err(Error::from)?;
+                DynDNS::pause(&conn, target.id, reason).await?;
+            }
         }
 
-        Ok(())
+        Ok(Some(outcome))
     }
 
-    async fn wait(&mut self, start_time: time::Instant, mut interval: time::Interval) {
+    async fn compare(
+        &self,
+        target: &DynDnsRecord,
+        detection: &Detection<'_>,
+        run_ipv4: bool,
+        run_ipv6: bool,
+        force: bool,
+    ) -> Result<(Ipv4CheckResult, Ipv6CheckResult), Error> {
+        if let Some(lookup) = target.config.compare_dns.as_ref() {
+            match self
+                .compare_dns(target, lookup, detection, run_ipv4, run_ipv6, force)
+                .await
+            {
+                Ok(results) => return Ok(results),
+                Err(err) => warn!(
+                    "[{}] could not resolve the published records, comparing with history: {}",
+                    target.config.hostname, err
+                ),
+            }
+        }
+
+        let ipv4_result = match &detection.ipv4 {
+            Some(current) if run_ipv4 => {
+                let previous = self.load_ipv4_history(target.id).await?;
+                detection.ipv4_checker.compare(previous, current, force)
+            }
+            _ => Ipv4CheckResult::default(),
+        };
+        let ipv6_result = match detection.ipv6.as_deref() {
+            Some(current) if run_ipv6 => {
+                let history = self.load_ipv6_history(target.id).await?;
+                let publish_all = target.config.options.publish_all_ipv6.unwrap_or(false);
+                match Ipv6Suffix::from_config(&target.config) {
+                    Some(suffix) => detection.ipv6_checker.compare_prefix(
+                        history,
+                        current,
+                        &suffix,
+                        force,
+                        publish_all,
+                    ),
+                    None => {
+                        detection
+                            .ipv6_checker
+                            .compare(history, current, force, publish_all)
+                            .await
+                    }
+                }
+            }
+            _ => Ipv6CheckResult::default(),
+        };
+        Ok((ipv4_result, ipv6_result))
+    }
+
+    /// Compares the detected addresses with what the hostnames resolve to.
+    async fn compare_dns(
+        &self,
+        target: &DynDnsRecord,
+        lookup: &RecordLookup,
+        detection: &Detection<'_>,
+        run_ipv4: bool,
+        run_ipv6: bool,
+        force: bool,
+    ) -> Result<(Ipv4CheckResult, Ipv6CheckResult), Error> {
+        let ipv4_result = match &detection.ipv4 {
+            Some(current) if run_ipv4 => {
+                let published = published_records(lookup, &target.config, TYPE_A).await?;
+                let previous = match published.as_slice() {
+                    [IpAddr::V4(address)] => Some(*address),
+                    _ => None,
+                };
+                detection.ipv4_checker.compare(previous, current, force)
+            }
+            _ => Ipv4CheckResult::default(),
+        };
+        let ipv6_result = match detection.ipv6.as_deref() {
+            Some(current) if run_ipv6 => {
+                let published = published_records(lookup, &target.config, TYPE_AAAA)
+                    .await?
+                    .into_iter()
+                    .filter_map(|address| match address {
+                        IpAddr::V6(address) => Some(address),
+                        IpAddr::V4(_) => None,
+                    })
+                    .collect();
+                let suffix = Ipv6Suffix::from_config(&target.config);
+                let publish_all = target.config.options.publish_all_ipv6.unwrap_or(false);
+                detection
+                    .ipv6_checker
+                    .compare_published(published, current, suffix.as_ref(), force, publish_all)
+                    .await
+            }
+            _ => Ipv6CheckResult::default(),
+        };
+        Ok((ipv4_result, ipv6_result))
+    }
+
+    /// Whether the last recorded update is older than the target's max age.
+    async fn refresh_due(&self, target: &DynDnsRecord) -> Result<bool, Error> {
+        let Some(max_age) = target.config.max_age else {
+            return Ok(false);
+        };
+        let conn = self.pool.get().await?;
+        let Some(last_updated) = History::last_updated(&conn, target.id).await? else {
+            return Ok(false);
+        };
+        Ok(Utc::now().naive_utc() - last_updated >= chrono::Duration::seconds(max_age))
+    }
+
+    fn attempt_for(outcome: UpdateOutcome, next_run_at: Option<NaiveDateTime>) -> DynDnsAttempt {
+        let next_run_at = match &outcome {
+            UpdateOutcome::Fatal(_) => None,
+            _ => next_run_at,
+        };
+        let (result, response) = outcome.into_attempt();
+        DynDnsAttempt {
+            result,
+            response,
+            next_run_at,
+            circuit_open: false,
+            provider_called: true,
+        }
+    }
+
+    /// Backs a failing target off exponentially and opens its circuit breaker
+    /// once `threshold` attempts in a row have failed. While open, the target is
+    /// retried once per cool-down; a success closes it again. A provider asking
+    /// to back off counts as a failure and waits at least `RETRY_LATER_DELAY`.
+    /// Errors before the provider was called neither count nor back off, as
+    /// detection is retried on the next cycle without touching the provider.
+    ///
+    /// Returns a message to record when the breaker has just opened.
+    fn track_failures(
+        &mut self,
+        id: i32,
+        hostname: &str,
+        attempt: &mut DynDnsAttempt,
+        threshold: u32,
+    ) -> Option<String> {
+        if attempt.result.is_success() {
+            if self
+                .failures
+                .remove(&id)
+                .is_some_and(|failures| failures >= threshold)
+            {
+                info!("[{}] circuit breaker closed", hostname);
+            }
+            return None;
+        }
+        if !attempt.provider_called {
+            attempt.circuit_open = self.failures.get(&id) >= Some(&threshold);
+            return None;
+        }
+        if !matches!(
+            attempt.result,
+            AttemptResult::Failed | AttemptResult::Error | AttemptResult::RetryLater
+        ) {
+            return None;
+        }
+
+        let failures = self.failures.entry(id).or_default();
+        *failures += 1;
+        let failures = *failures;
+        let mut delay = if failures >= threshold {
+            BACKOFF_MAX
+        } else {
+            backoff_delay(failures)
+        };
+        if attempt.result == AttemptResult::RetryLater {
+            delay = delay.max(RETRY_LATER_DELAY);
+        }
+        self.retry_after.insert(id, time::Instant::now() + delay);
+        attempt.next_run_at = Some(Utc::now().naive_utc() + delay);
+        attempt.circuit_open = failures >= threshold;
+
+        if failures == threshold {
+            let message = format!(
+                "[{}] circuit breaker opened after {} consecutive failures, retrying in {}s",
+                hostname,
+                failures,
+                delay.as_secs()
+            );
+            warn!("{}", message);
+            Some(message)
+        } else {
+            debug!(
+                "[{}] attempt {} failed, retrying in {}s",
+                hostname,
+                failures,
+                delay.as_secs()
+            );
+            None
+        }
+    }
+
+    async fn record_status(
+        &self,
+        dyndns_id: i32,
+        attempt: Option<DynDnsAttempt>,
+        next_run_at: Option<NaiveDateTime>,
+        reachability: Option<Ipv4Reachability>,
+    ) -> Result<(), Error> {
+        let conn = self.pool.get().await?;
+        match attempt {
+            Some(attempt) => {
+                DynDnsStatus::record_attempt(&conn, dyndns_id, attempt, reachability).await
+            }
+            None => DynDnsStatus::record_check(&conn, dyndns_id, next_run_at, reachability).await,
+        }
+    }
+
+    async fn wait(
+        &mut self,
+        start_time: time::Instant,
+        mut interval: time::Interval,
+    ) -> Option<TriggerRequest> {
+        let retry_at = self.retry_after.values().min().copied();
         loop {
             tokio::select! {
                 _ = interval.tick() => {
-                    return;
+                    return None;
+                },
+                _ = sleep_until(retry_at) => {
+                    debug!("retry due");
+                    return None;
+                },
+                Some(request) = self.trigger_rx.recv() => {
+                    debug!("cycle requested, force: {}", request.force);
+                    return Some(request);
+                },
+                Some(index) = self.address_rx.recv() => {
+                    if self.watched.contains(&index) {
+                        self.debounce_address_events().await;
+                        debug!("address change on interface {}", index);
+                        return None;
+                    }
                 },
                 Ok(_) = self.interval_rx.changed() => {
                     self.interval_secs = *self.interval_rx.borrow();
@@ -130,15 +704,36 @@ impl DynDnsScheduler {
         }
     }
 
-    async fn load_config(&self) -> Result<DynDNS, Error> {
+    async fn debounce_address_events(&mut self) {
+        while let Ok(Some(_)) = time::timeout(ADDRESS_EVENT_DEBOUNCE, self.address_rx.recv()).await
+        {
+        }
+    }
+
+    async fn record_event(&self, level: EventLevel, message: String) {
+        let result = match self.pool.get().await {
+            Ok(conn) => Event::insert(&conn, level, message).await,
+            Err(err) => Err(err.into()),
+        };
+        if let Err(err) = result {
+            error!("{}", err);
+        }
+    }
+
+    async fn load_settings(&self) -> Result<Settings, Error> {
         let conn = self.pool.get().await?;
-        DynDNS::get(&conn).await
+        Settings::get(&conn).await
+    }
+
+    async fn load_targets(&self) -> Result<Vec<DynDnsRecord>, Error> {
+        let conn = self.pool.get().await?;
+        DynDNS::list(&conn).await
     }
 
     async fn load_interval_seconds(pool: &DbPool) -> u64 {
         match pool.get().await {
-            Ok(conn) => match DynDNS::get_sleep_interval(&conn).await {
-                Ok(value) => value.into(),
+            Ok(conn) => match Settings::get(&conn).await {
+                Ok(settings) => settings.sleep_interval.into(),
                 Err(err) => {
                     error!("{}", err);
                     10
@@ -151,17 +746,18 @@ impl DynDnsScheduler {
         }
     }
 
-    async fn load_ipv4_history(&self) -> Result<Option<std::net::Ipv4Addr>, Error> {
-        use std::net::Ipv4Addr;
-
+    async fn load_ipv4_history(&self, dyndns_id: i32) -> Result<Option<Ipv4Addr>, Error> {
         let conn = self.pool.get().await?;
-        let record = History::get_v4(&conn).await?;
+        let record = History::get_v4(&conn, dyndns_id).await?;
         Ok(record.and_then(|value| value.parse::<Ipv4Addr>().ok()))
     }
 
-    async fn load_ipv6_history(&self) -> Result<Option<Ipv6HistorySnapshot>, Error> {
+    async fn load_ipv6_history(
+        &self,
+        dyndns_id: i32,
+    ) -> Result<Option<Ipv6HistorySnapshot>, Error> {
         let conn = self.pool.get().await?;
-        let record = History::get_v6(&conn).await?;
+        let record = History::get_v6(&conn, dyndns_id).await?;
         Ok(record.map(|(previous, latest)| {
             let previous_parsed = previous.map(|value| parse_ipv6_list(&value));
             let latest_parsed = parse_ipv6_list(&latest);
@@ -169,21 +765,266 @@ impl DynDnsScheduler {
         }))
     }
 
+    /// Returns the ids of the inserted IPv4 and IPv6 history entries.
     async fn persist_history(
         &self,
+        target: &DynDnsRecord,
         ipv4: &Ipv4CheckResult,
         ipv6: &Ipv6CheckResult,
-    ) -> Result<(), Error> {
+        event: HistoryEvent,
+    ) -> Result<(Option<i32>, Option<i32>), Error> {
         let conn = self.pool.get().await?;
 
+        let mut ipv4_id = None;
         if let Some(new) = ipv4.current.as_ref() {
-            History::insert_v4(&conn, &ipv4.previous, new).await?;
+            ipv4_id = Some(History::insert_v4(&conn, target.id, &ipv4.previous, new, event).await?);
         }
 
+        let mut ipv6_id = None;
         if let Some(new) = ipv6.current.as_ref() {
-            History::insert_v6(&conn, &ipv6.previous, new).await?;
+            let prefix = Ipv6Suffix::from_config(&target.config).and_then(|suffix| {
+                let old = ipv6
+                    .previous
+                    .as_ref()
+                    .and_then(|previous| previous.first())
+                    .map(|address| suffix.prefix(address));
+                new.first().map(|address| (old, suffix.prefix(address)))
+            });
+            ipv6_id = Some(
+                History::insert_v6(&conn, target.id, &ipv6.previous, new, event, prefix).await?,
+            );
         }
 
-        Ok(())
+        Ok((ipv4_id, ipv6_id))
+    }
+
+    /// Checks in the background that the published addresses resolve and stores
+    /// the result on the history entries, so the cycle is not held up by propagation.
+    fn spawn_verification(
+        &self,
+        target: &DynDnsRecord,
+        ipv4: Option<(i32, Vec<IpAddr>)>,
+        ipv6: Option<(i32, Vec<IpAddr>)>,
+    ) {
+        let Some(lookup) = target.config.verify.clone() else {
+            return;
+        };
+        let pool = self.pool.clone();
+        let hostname = target.config.hostname.clone();
+        let verifier = DnsVerifier::new(
+            lookup,
+            target.config.hostnames().map(str::to_owned).collect(),
+        );
+        tokio::spawn(async move {
+            let verify = |entry: Option<(i32, Vec<IpAddr>)>| async {
+                let (id, expected) = entry?;
+                Some((id, verifier.verify(&expected).await))
+            };
+            let (ipv4, ipv6) = tokio::join!(verify(ipv4), verify(ipv6));
+
+            let conn = match pool.get().await {
+                Ok(conn) => conn,
+                Err(err) => {
+                    error!("[{}] {}", hostname, err);
+                    return;
+                }
+            };
+            for (id, verified) in [ipv4, ipv6].into_iter().flatten() {
+                if verified {
+                    info!("[{}] update verified in dns", hostname);
+                } else {
+                    let message = format!(
+                        "[{}] published address did not show up in dns in time",
+                        hostname
+                    );
+                    warn!("{}", message);
+                    if let Err(err) = Event::insert(&conn, EventLevel::Warning, message).await {
+                        error!("{}", err);
+                    }
+                }
+                if let Err(err) = History::set_verified(&conn, vec![id], verified).await {
+                    error!("[{}] {}", hostname, err);
+                }
+            }
+        });
+    }
+}
+
+/// Records of `rtype` shared by all of the target's hostnames; empty when they
+/// disagree, so that the update brings them back in line.
+async fn published_records(
+    lookup: &RecordLookup,
+    config: &DynDNS,
+    rtype: u16,
+) -> Result<Vec<IpAddr>, Error> {
+    let mut shared: Option<Vec<IpAddr>> = None;
+    for hostname in config.hostnames() {
+        let mut addresses = published_addresses(lookup, hostname, rtype).await?;
+        addresses.sort();
+        debug!("[{}] published {:?}", hostname, addresses);
+        match &shared {
+            Some(shared) if *shared != addresses => return Ok(vec![]),
+            Some(_) => {}
+            None => shared = Some(addresses),
+        }
+    }
+    Ok(shared.unwrap_or_default())
+}
+
+/// Equal-jitter exponential backoff: half of the capped delay is fixed, the
+/// other half random, so failing targets do not retry in lockstep.
+fn backoff_delay(failures: u32) -> Duration {
+    let delay = BACKOFF_BASE
+        .saturating_mul(1 << failures.saturating_sub(1).min(16))
+        .min(BACKOFF_MAX);
+    let half = delay / 2;
+    half + half.mul_f64(f64::from(OsRng.next_u32()) / f64::from(u32::MAX))
+}
+
+async fn sleep_until(deadline: Option<time::Instant>) {
+    match deadline {
+        Some(deadline) => time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Addresses detected on one interface, shared by every target using it.
+pub(super) struct Detection<'a> {
+    pub(super) ipv4_checker: Ipv4Checker<'a>,
+    pub(super) ipv6_checker: Ipv6Checker<'a>,
+    pub(super) ipv4: Option<LookupAnswer<Ipv4Addr>>,
+    pub(super) ipv6: Option<Vec<Ipv6Addr>>,
+}
+
+impl<'a> Detection<'a> {
+    pub(super) fn take_warnings(&self) -> Vec<String> {
+        let mut warnings = self.ipv4_checker.take_warnings();
+        warnings.extend(self.ipv6_checker.take_warnings());
+        warnings
+    }
+
+    /// Sets the checkers up without detecting anything yet.
+    pub(super) fn new(client: &'a HttpClient, interface: &'a str, settings: &'a Settings) -> Self {
+        let quorum = settings.lookup_quorum as usize;
+        let ipv4_checker = Ipv4Checker::new(
+            interface,
+            settings.ipv4_detection,
+            settings.reject_private_ipv4,
+            ExternalIpLookup::new(
+                client,
+                interface,
+                &settings.ipv4_lookup,
+                settings.lookup_mode,
+                quorum,
+            ),
+        );
+        let ipv6_checker = Ipv6Checker::new(
+            interface,
+            settings.ipv6_selection,
+            ExternalIpLookup::new(
+                client,
+                interface,
+                &settings.ipv6_lookup,
+                settings.lookup_mode,
+                quorum,
+            ),
+        );
+
+        Self {
+            ipv4_checker,
+            ipv6_checker,
+            ipv4: None,
+            ipv6: None,
+        }
+    }
+
+    async fn run(
+        client: &'a HttpClient,
+        interface: &'a str,
+        settings: &'a Settings,
+        run_ipv4: bool,
+        run_ipv6: bool,
+    ) -> Detection<'a> {
+        let mut detection = Self::new(client, interface, settings);
+        let (ipv4, ipv6) = match (run_ipv4, run_ipv6) {
+            (true, true) => tokio::join!(
+                run_checker(&detection.ipv4_checker),
+                run_checker(&detection.ipv6_checker)
+            ),
+            (true, false) => (run_checker(&detection.ipv4_checker).await, None),
+            (false, true) => (None, run_checker(&detection.ipv6_checker).await),
+            (false, false) => (None, None),
+        };
+        detection.ipv4 = ipv4;
+        detection.ipv6 = ipv6;
+        detection
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler() -> DynDnsScheduler {
+        let manager =
+            deadpool_diesel::sqlite::Manager::new(":memory:", deadpool_diesel::Runtime::Tokio1);
+        DynDnsScheduler {
+            pool: deadpool_diesel::sqlite::Pool::builder(manager)
+                .build()
+                .unwrap(),
+            client: HttpClient::new(1, Duration::ZERO),
+            interval_rx: watch::channel(300).1,
+            trigger_rx: mpsc::channel(1).1,
+            shutdown_rx: watch::channel(false).1,
+            address_rx: mpsc::channel(1).1,
+            watched: HashSet::new(),
+            interval_secs: 300,
+            retry_after: HashMap::new(),
+            failures: HashMap::new(),
+        }
+    }
+
+    fn failed_attempt(result: AttemptResult, provider_called: bool) -> DynDnsAttempt {
+        DynDnsAttempt {
+            result,
+            response: None,
+            next_run_at: None,
+            circuit_open: false,
+            provider_called,
+        }
+    }
+
+    #[tokio::test]
+    async fn detection_failures_do_not_open_breaker() {
+        let mut scheduler = scheduler();
+        for _ in 0..3 {
+            let mut attempt = failed_attempt(AttemptResult::Error, false);
+            assert_eq!(
+                scheduler.track_failures(1, "example.com", &mut attempt, 2),
+                None
+            );
+            assert!(!attempt.circuit_open);
+        }
+        assert!(scheduler.failures.is_empty());
+        assert!(scheduler.retry_after.is_empty());
+
+        let mut attempt = failed_attempt(AttemptResult::Error, true);
+        assert_eq!(
+            scheduler.track_failures(1, "example.com", &mut attempt, 2),
+            None
+        );
+        let mut attempt = failed_attempt(AttemptResult::Failed, true);
+        assert!(
+            scheduler
+                .track_failures(1, "example.com", &mut attempt, 2)
+                .is_some()
+        );
+        assert!(attempt.circuit_open);
+
+        // An open breaker stays open through a failed detection.
+        let mut attempt = failed_attempt(AttemptResult::Error, false);
+        scheduler.track_failures(1, "example.com", &mut attempt, 2);
+        assert!(attempt.circuit_open);
+        assert_eq!(scheduler.failures[&1], 2);
     }
 }
@@ -0,0 +1,131 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use serde::Serialize;
+
+use crate::util::percent_encode;
+
+const REDACTED: &str = "[redacted]";
+
+/// Requests and responses exchanged with lookup sources and providers during
+/// a test run, with the target's secret removed.
+#[derive(Clone)]
+pub struct Trace {
+    entries: Arc<Mutex<Vec<TraceEntry>>>,
+    secrets: Arc<Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TraceEntry {
+    Http {
+        method: String,
+        url: String,
+        headers: BTreeMap<String, String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        body: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        response: Option<HttpResponseTrace>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    Dns {
+        server: String,
+        request: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        response: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct HttpResponseTrace {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+}
+
+impl Trace {
+    /// `secret` is replaced wherever it shows up, as is and percent-encoded.
+    pub fn new(secret: &str) -> Self {
+        let mut secrets = vec![];
+        if !secret.is_empty() {
+            secrets.push(secret.to_owned());
+            let encoded = percent_encode(secret);
+            if encoded != secret {
+                secrets.push(encoded);
+            }
+        }
+        Self {
+            entries: Arc::new(Mutex::new(vec![])),
+            secrets: Arc::new(secrets),
+        }
+    }
+
+    pub fn record(&self, mut entry: TraceEntry) {
+        match &mut entry {
+            TraceEntry::Http {
+                url,
+                headers,
+                body,
+                response,
+                error,
+                ..
+            } => {
+                self.redact(url);
+                for (name, value) in headers.iter_mut() {
+                    if is_sensitive(name) {
+                        *value = REDACTED.to_owned();
+                    } else {
+                        self.redact(value);
+                    }
+                }
+                body.iter_mut()
+                    .chain(error.iter_mut())
+                    .for_each(|text| self.redact(text));
+                if let Some(response) = response {
+                    response
+                        .headers
+                        .values_mut()
+                        .for_each(|text| self.redact(text));
+                    self.redact(&mut response.body);
+                }
+            }
+            TraceEntry::Dns {
+                request,
+                response,
+                error,
+                ..
+            } => {
+                self.redact(request);
+                response
+                    .iter_mut()
+                    .chain(error.iter_mut())
+                    .for_each(|text| self.redact(text));
+            }
+        }
+        self.entries.lock().unwrap().push(entry);
+    }
+
+    pub fn take(&self) -> Vec<TraceEntry> {
+        std::mem::take(&mut *self.entries.lock().unwrap())
+    }
+
+    pub fn redact(&self, text: &mut String) {
+        for secret in self.secrets.iter() {
+            if text.contains(secret.as_str()) {
+                *text = text.replace(secret.as_str(), REDACTED);
+            }
+        }
+    }
+}
+
+fn is_sensitive(header: &str) -> bool {
+    matches!(
+        header.to_ascii_lowercase().as_str(),
+        "authorization" | "proxy-authorization" | "cookie" | "set-cookie" | "x-auth-key"
+    )
+}
@@ -1,151 +1,91 @@
-use std::{
-    fmt::Display,
-    net::{Ipv4Addr, Ipv6Addr},
-};
+use std::time::Duration;
 
-use isahc::{
-    Request,
-    auth::{Authentication, Credentials},
-    config::Configurable,
-    prelude::AsyncReadResponseExt,
+use crate::{
+    Error,
+    db::{DynDNS, Ipv4Reachability},
 };
-use serde::{Serialize, Serializer};
-
-use crate::Error;
-
-use crate::db::DynDNS;
 
 use super::{
     checker::{ipv4::Ipv4CheckResult, ipv6::Ipv6CheckResult},
     http_client::HttpClient,
+    provider::{DnsProvider, Provider, UpdateOutcome},
 };
 
-const DYNDNS_GOOD: &str = "good";
-
 pub struct DynDnsUpdater<'a> {
-    client: &'a HttpClient,
-    auth: DynDnsAuth<'a>,
+    hostname: &'a str,
+    provider: Provider<'a>,
 }
 
 impl<'a> DynDnsUpdater<'a> {
-    pub fn new(client: &'a HttpClient, auth: DynDnsAuth<'a>) -> Self {
-        Self { client, auth }
+    pub fn new(client: &'a HttpClient, config: &'a DynDNS) -> Result<Self, Error> {
+        Ok(Self {
+            hostname: config.hostname.as_str(),
+            provider: Provider::new(client, config)?,
+        })
     }
 
     pub async fn apply(
         &self,
         ipv4: &Ipv4CheckResult,
         ipv6: &Ipv6CheckResult,
-    ) -> Result<bool, Error> {
+    ) -> Result<Option<UpdateOutcome>, Error> {
         let ipv4_changed = ipv4.external.is_some();
         let ipv6_changed = ipv6.external.is_some();
 
         if !ipv4_changed && !ipv6_changed {
-            return Ok(false);
-        }
-
-        let myip = MyIp::new(ipv4.external.as_ref(), ipv6.external.as_ref());
-        let params = DynDnsParams::new(self.auth.hostname, myip);
-
-        let ip_summary = params.myip.to_string();
-        info!("ip address changed, start update: {}", ip_summary);
-
-        if self.update(params).await? {
-            info!("Successful update!");
-            return Ok(true);
+            return Ok(None);
         }
 
-        Ok(false)
-    }
-
-    async fn update(&self, params: DynDnsParams<'_, '_>) -> Result<bool, Error> {
-        let url = format!(
-            "https://{server}/nic/update?hostname={hostname}&myip={myip}",
-            server = self.auth.server,
-            hostname = params.hostname,
-            myip = params.myip
+        let ip_summary = [ipv4.summary(), ipv6.summary()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(",");
+        info!(
+            "[{}] ip address changed, start update: {}",
+            self.hostname, ip_summary
         );
 
-        let request = Request::get(url)
-            .authentication(Authentication::basic())
-            .credentials(Credentials::new(self.auth.username, self.auth.password))
-            .body(())
-            .unwrap();
-
-        let mut response = self.client.send_async(request).await?;
-        let status = response.status();
-        let body = response.text().await?;
-        let message = body.trim().to_string();
-
-        if status.is_success() && message == DYNDNS_GOOD {
-            debug!("{}", DYNDNS_GOOD);
-            Ok(true)
-        } else {
-            error!("code: {status}, msg: {message}");
-            Ok(false)
+        match ipv4.reachability {
+            Some(Ipv4Reachability::Nat) => warn!(
+                "[{}] interface is behind NAT, inbound connections may not reach the published ipv4",
+                self.hostname
+            ),
+            Some(Ipv4Reachability::Cgnat) => warn!(
+                "[{}] interface is behind carrier-grade NAT, inbound connections will not reach the published ipv4",
+                self.hostname
+            ),
+            Some(Ipv4Reachability::Direct) | None => {}
         }
-    }
-}
-
-#[derive(Default)]
-struct MyIp<'a> {
-    v4: Option<&'a Ipv4Addr>,
-    v6: Option<&'a Ipv6Addr>,
-}
-
-impl<'a> MyIp<'a> {
-    fn new(v4: Option<&'a Ipv4Addr>, v6: Option<&'a Ipv6Addr>) -> Self {
-        Self { v4, v6 }
-    }
-
-    fn serialize<S>(value: &Self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        serializer.serialize_str(value.to_string().as_str())
-    }
-}
 
-impl<'a> Display for MyIp<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut parts = vec![];
-        if let Some(ip) = self.v4 {
-            parts.push(ip.to_string());
+        let outcome = self.provider.update(ipv4, ipv6).await?;
+        match &outcome {
+            UpdateOutcome::Updated => info!("[{}] Successful update!", self.hostname),
+            UpdateOutcome::Unchanged => info!("[{}] address already up to date", self.hostname),
+            UpdateOutcome::Failed(reason) => {
+                error!("[{}] update failed: {}", self.hostname, reason)
+            }
+            UpdateOutcome::RetryLater(reason) => {
+                warn!("[{}] provider asked to back off: {}", self.hostname, reason)
+            }
+            UpdateOutcome::Fatal(reason) => {
+                error!("[{}] provider rejected update: {}", self.hostname, reason)
+            }
         }
-        if let Some(ip) = self.v6 {
-            parts.push(ip.to_string());
-        }
-        write!(f, "{}", parts.join(","))
-    }
-}
-
-#[derive(Serialize)]
-struct DynDnsParams<'a, 'b> {
-    hostname: &'a str,
-    #[serde(serialize_with = "MyIp::serialize")]
-    myip: MyIp<'b>,
-}
 
-impl<'a, 'b> DynDnsParams<'a, 'b> {
-    fn new(hostname: &'a str, myip: MyIp<'b>) -> Self {
-        Self { hostname, myip }
+        Ok(Some(outcome))
     }
 }
 
-pub struct DynDnsAuth<'a> {
-    pub server: &'a str,
-    pub username: &'a str,
-    pub password: &'a str,
-    pub hostname: &'a str,
-}
-
-impl<'a> From<&'a DynDNS> for DynDnsAuth<'a> {
-    fn from(value: &'a DynDNS) -> Self {
-        Self {
-            server: value.server.as_str(),
-            username: value.username.as_str(),
-            password: value.password.as_str(),
-            hostname: value.hostname.as_str(),
-        }
-    }
+/// Removes the records published for `config` where the provider supports it.
+pub async fn clear_records(config: &DynDNS) -> Result<(), Error> {
+    let client = HttpClient::new(3, Duration::from_millis(200));
+    let Some(detail) = Provider::new(&client, config)?.clear().await? else {
+        return Err(Error::validation_failed(format!(
+            "{} does not support clearing records",
+            config.provider.as_str()
+        )));
+    };
+    info!("[{}] {}", config.hostname, detail);
+    Ok(())
 }
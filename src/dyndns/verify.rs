@@ -0,0 +1,180 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use rand_core::{OsRng, RngCore};
+use tokio::time::{self, Instant};
+
+use crate::{Error, db::RecordLookup};
+
+use super::dns::{
+    exchange,
+    message::{
+        CLASS_IN, Header, MessageBuilder, OPCODE_QUERY, Record, TYPE_A, TYPE_AAAA, TYPE_NS,
+        answers, rcode_name, read_name,
+    },
+    resolve_server,
+};
+
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(120);
+const VERIFY_INTERVAL: Duration = Duration::from_secs(10);
+const RCODE_NXDOMAIN: u16 = 3;
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// Looks the published records up after an update until they carry the new
+/// addresses or the timeout passes.
+pub struct DnsVerifier {
+    lookup: RecordLookup,
+    hostnames: Vec<String>,
+}
+
+impl DnsVerifier {
+    pub fn new(lookup: RecordLookup, hostnames: Vec<String>) -> Self {
+        Self { lookup, hostnames }
+    }
+
+    /// Whether every hostname returns all of `expected` from every server in time.
+    pub async fn verify(&self, expected: &[IpAddr]) -> bool {
+        let Some(first) = expected.first() else {
+            return true;
+        };
+        let rtype = match first {
+            IpAddr::V4(_) => TYPE_A,
+            IpAddr::V6(_) => TYPE_AAAA,
+        };
+
+        let deadline = Instant::now() + VERIFY_TIMEOUT;
+        loop {
+            match self.check(rtype, expected).await {
+                Ok(true) => return true,
+                Ok(false) => {}
+                Err(err) => debug!("dns verification: {}", err),
+            }
+            if Instant::now() + VERIFY_INTERVAL > deadline {
+                return false;
+            }
+            time::sleep(VERIFY_INTERVAL).await;
+        }
+    }
+
+    async fn check(&self, rtype: u16, expected: &[IpAddr]) -> Result<bool, Error> {
+        for hostname in &self.hostnames {
+            let (servers, recursive) = servers(&self.lookup, hostname).await?;
+            for server in servers {
+                let (_, records) = query(server, hostname, rtype, recursive).await?;
+                let published: Vec<IpAddr> = records.iter().filter_map(record_address).collect();
+                if let Some(missing) = expected.iter().find(|addr| !published.contains(addr)) {
+                    debug!(
+                        "{} does not return {} for {} yet",
+                        server, missing, hostname
+                    );
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// What `hostname` currently resolves to for `rtype`, as answered by the
+/// first server that responds.
+pub async fn published_addresses(
+    lookup: &RecordLookup,
+    hostname: &str,
+    rtype: u16,
+) -> Result<Vec<IpAddr>, Error> {
+    let (servers, recursive) = servers(lookup, hostname).await?;
+    let mut last_error = None;
+    for server in servers {
+        match query(server, hostname, rtype, recursive).await {
+            Ok((_, records)) => return Ok(records.iter().filter_map(record_address).collect()),
+            Err(err) => last_error = Some(err),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| Error::dns(format!("no nameservers found for {hostname}"))))
+}
+
+/// Servers to ask for the records of `hostname` and whether to ask for recursion.
+async fn servers(lookup: &RecordLookup, hostname: &str) -> Result<(Vec<SocketAddr>, bool), Error> {
+    Ok(match lookup {
+        RecordLookup::Resolver { server } => (vec![resolve_server(server).await?], true),
+        RecordLookup::Authoritative => (nameservers(hostname).await?, false),
+    })
+}
+
+/// Finds the NS set of the closest enclosing zone through the system resolver.
+async fn nameservers(hostname: &str) -> Result<Vec<SocketAddr>, Error> {
+    let resolver = system_resolver().await?;
+    let mut zone = hostname;
+    loop {
+        let (response, records) = query(resolver, zone, TYPE_NS, true).await?;
+        let mut servers = vec![];
+        for record in records.iter().filter(|record| record.rtype == TYPE_NS) {
+            let name = read_name(&response, record.offset)?;
+            servers.push(resolve_server(&name).await?);
+        }
+        if !servers.is_empty() {
+            debug!("nameservers of {}: {:?}", zone, servers);
+            return Ok(servers);
+        }
+        match zone.split_once('.') {
+            Some((_, parent)) if parent.contains('.') => zone = parent,
+            _ => return Err(Error::dns(format!("no nameservers found for {hostname}"))),
+        }
+    }
+}
+
+async fn system_resolver() -> Result<SocketAddr, Error> {
+    let config = tokio::fs::read_to_string(RESOLV_CONF).await?;
+    let server = config
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .map(str::trim)
+        .find(|server| !server.is_empty())
+        .ok_or_else(|| Error::dns(format!("no nameserver in {RESOLV_CONF}")))?;
+    resolve_server(server).await
+}
+
+/// Sends a query and returns the response with its answers; a missing name
+/// yields no answers.
+async fn query(
+    server: SocketAddr,
+    name: &str,
+    rtype: u16,
+    recursive: bool,
+) -> Result<(Vec<u8>, Vec<Record>), Error> {
+    let mut builder = MessageBuilder::new(OsRng.next_u32() as u16, OPCODE_QUERY);
+    if recursive {
+        builder.recursion_desired();
+    }
+    builder.question(name, rtype, CLASS_IN)?;
+    let response = exchange(server, None, &builder.finish()).await?;
+
+    let header = Header::parse(&response)?;
+    match header.rcode() {
+        0 => {
+            let records = answers(&response)?;
+            Ok((response, records))
+        }
+        RCODE_NXDOMAIN => Ok((response, vec![])),
+        rcode => Err(Error::dns(format!(
+            "{} query for {} failed: {}",
+            server,
+            name,
+            rcode_name(rcode)
+        ))),
+    }
+}
+
+fn record_address(record: &Record) -> Option<IpAddr> {
+    match record.rtype {
+        TYPE_A => <[u8; 4]>::try_from(record.rdata.as_slice())
+            .ok()
+            .map(|octets| IpAddr::V4(Ipv4Addr::from(octets))),
+        TYPE_AAAA => <[u8; 16]>::try_from(record.rdata.as_slice())
+            .ok()
+            .map(|octets| IpAddr::V6(Ipv6Addr::from(octets))),
+        _ => None,
+    }
+}
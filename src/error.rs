@@ -49,6 +49,32 @@ pub enum DynDnsError {
     ValidationFailed(String),
     #[error(transparent)]
     SleepInterval(#[from] SleepIntervalError),
+    #[error(transparent)]
+    Provider(#[from] ProviderError),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProviderError {
+    #[error("{provider}: zone not found for {hostname}")]
+    ZoneNotFound {
+        provider: &'static str,
+        hostname: String,
+    },
+    #[error("{provider}: api error: {message}")]
+    Api {
+        provider: &'static str,
+        message: String,
+    },
+    #[error("{provider}: unexpected response: {message}")]
+    InvalidResponse {
+        provider: &'static str,
+        message: String,
+    },
+    #[error("{provider}: request rejected: {message}")]
+    Rejected {
+        provider: &'static str,
+        message: String,
+    },
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -61,10 +87,20 @@ pub enum NetworkError {
     Ipv6NotFound,
     #[error("ipv4 not found")]
     Ipv4NotFound,
+    #[error("refusing to publish non-public ipv4 address {0}")]
+    Ipv4NotPublic(String),
     #[error("Failed to parse IPv4 address : {0}")]
     IPv4ParseError(String),
     #[error("Failed to parse IPv6 address : {0}")]
     IPv6ParseError(String),
+    #[error("DNS Error: {0}")]
+    Dns(String),
+    #[error("STUN Error: {0}")]
+    Stun(String),
+    #[error("Netlink Error: {0}")]
+    Netlink(String),
+    #[error("{0}")]
+    NoQuorum(String),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -73,6 +109,8 @@ pub enum SystemError {
     Join(#[from] JoinError),
     #[error("{0}")]
     Io(#[from] std::io::Error),
+    #[error("DynDNS scheduler is not running")]
+    SchedulerUnavailable,
 }
 
 #[derive(Serialize)]
@@ -120,6 +158,30 @@ impl Error {
         AuthError::TokenEncodingFailed(reason.into()).into()
     }
 
+    pub fn provider_api(provider: &'static str, message: impl Into<String>) -> Self {
+        ProviderError::Api {
+            provider,
+            message: message.into(),
+        }
+        .into()
+    }
+
+    pub fn provider_invalid_response(provider: &'static str, message: impl Into<String>) -> Self {
+        ProviderError::InvalidResponse {
+            provider,
+            message: message.into(),
+        }
+        .into()
+    }
+
+    pub fn provider_rejected(provider: &'static str, message: impl Into<String>) -> Self {
+        ProviderError::Rejected {
+            provider,
+            message: message.into(),
+        }
+        .into()
+    }
+
     pub fn ipv4_parse_error(input: impl Into<String>) -> Self {
         NetworkError::IPv4ParseError(input.into()).into()
     }
@@ -128,6 +190,26 @@ impl Error {
         NetworkError::IPv6ParseError(input.into()).into()
     }
 
+    pub fn dns(reason: impl Into<String>) -> Self {
+        NetworkError::Dns(reason.into()).into()
+    }
+
+    pub fn stun(reason: impl Into<String>) -> Self {
+        NetworkError::Stun(reason.into()).into()
+    }
+
+    pub fn netlink(reason: impl Into<String>) -> Self {
+        NetworkError::Netlink(reason.into()).into()
+    }
+
+    pub fn no_quorum(reason: impl Into<String>) -> Self {
+        NetworkError::NoQuorum(reason.into()).into()
+    }
+
+    pub fn ipv4_not_public(address: impl Into<String>) -> Self {
+        NetworkError::Ipv4NotPublic(address.into()).into()
+    }
+
     pub fn ipv6_not_found() -> Self {
         NetworkError::Ipv6NotFound.into()
     }
@@ -136,6 +218,10 @@ impl Error {
         NetworkError::Ipv4NotFound.into()
     }
 
+    pub fn scheduler_unavailable() -> Self {
+        Error::System(SystemError::SchedulerUnavailable)
+    }
+
     fn status_code(&self) -> StatusCode {
         match self {
             Error::Database(DatabaseError::Diesel(DieselError::NotFound)) => StatusCode::NOT_FOUND,
@@ -143,6 +229,8 @@ impl Error {
             Error::DynDns(DynDnsError::NotConfigured) => StatusCode::NOT_FOUND,
             Error::DynDns(DynDnsError::ValidationFailed(_)) => StatusCode::BAD_REQUEST,
             Error::DynDns(DynDnsError::SleepInterval(_)) => StatusCode::BAD_REQUEST,
+            Error::DynDns(DynDnsError::Provider(_)) => StatusCode::BAD_GATEWAY,
+            Error::System(SystemError::SchedulerUnavailable) => StatusCode::SERVICE_UNAVAILABLE,
             Error::Auth(AuthError::TokenEncodingFailed(_)) => StatusCode::INTERNAL_SERVER_ERROR,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
@@ -163,18 +251,30 @@ impl Error {
                 DynDnsError::NotConfigured => Some("dyndns_not_configured"),
                 DynDnsError::ValidationFailed(_) => Some("validation_failed"),
                 DynDnsError::SleepInterval(_) => Some("invalid_sleep_interval"),
+                DynDnsError::Provider(provider) => match provider {
+                    ProviderError::ZoneNotFound { .. } => Some("provider_zone_not_found"),
+                    ProviderError::Api { .. } => Some("provider_api_error"),
+                    ProviderError::InvalidResponse { .. } => Some("provider_invalid_response"),
+                    ProviderError::Rejected { .. } => Some("provider_rejected"),
+                },
             },
             Error::Network(net) => match net {
                 NetworkError::Http(_) => Some("http_client_error"),
                 NetworkError::Interface(_) => Some("interface_error"),
                 NetworkError::Ipv6NotFound => Some("ipv6_not_found"),
                 NetworkError::Ipv4NotFound => Some("ipv4_not_found"),
+                NetworkError::Ipv4NotPublic(_) => Some("ipv4_not_public"),
                 NetworkError::IPv4ParseError(_) => Some("ipv4_parse_error"),
                 NetworkError::IPv6ParseError(_) => Some("ipv6_parse_error"),
+                NetworkError::Dns(_) => Some("dns_error"),
+                NetworkError::Stun(_) => Some("stun_error"),
+                NetworkError::Netlink(_) => Some("netlink_error"),
+                NetworkError::NoQuorum(_) => Some("lookup_quorum_not_reached"),
             },
             Error::System(system) => match system {
                 SystemError::Join(_) => Some("internal_error"),
                 SystemError::Io(_) => Some("io_error"),
+                SystemError::SchedulerUnavailable => Some("scheduler_unavailable"),
             },
         }
     }
@@ -192,6 +292,12 @@ impl From<SleepIntervalError> for Error {
     }
 }
 
+impl From<ProviderError> for Error {
+    fn from(err: ProviderError) -> Self {
+        Error::DynDns(err.into())
+    }
+}
+
 impl From<DeadPoolError> for Error {
     fn from(err: DeadPoolError) -> Self {
         Error::Database(err.into())
@@ -6,6 +6,8 @@ use std::{env, path::PathBuf, sync::Arc};
 use axum::{Router, extract::FromRef};
 
 use axum_extra::middleware::option_layer;
+use deadpool_diesel::sqlite::{Hook, HookError};
+use diesel::RunQueryDsl;
 use dotenvy::dotenv;
 use tokio::{net::TcpListener, signal, sync::watch};
 use tower_http::{
@@ -54,9 +56,11 @@ async fn main() {
     let cors = option_layer(cors);
     let (interval_tx, interval_rx) = watch::channel(0u64);
     let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let (scheduler, trigger_rx) = dyndns::trigger_channel();
     let state = AppState {
         pool: pool.clone(),
         interval_tx,
+        scheduler,
         auth,
     };
     let web_dir = PathBuf::from(&CONFIG.web_dir);
@@ -76,7 +80,12 @@ async fn main() {
     let listener = TcpListener::bind(config::CONFIG.addr).await.unwrap();
     let local_addr = listener.local_addr().unwrap();
     info!("listening on http://{}", local_addr);
-    let worker = tokio::spawn(dyndns::launch(pool, interval_rx, shutdown_rx.clone()));
+    let worker = tokio::spawn(dyndns::launch(
+        pool,
+        interval_rx,
+        trigger_rx,
+        shutdown_rx.clone(),
+    ));
     if let Err(err) = axum::serve(listener, app)
         .with_graceful_shutdown(shutdown_signal())
         .await
@@ -98,6 +107,7 @@ async fn main() {
 pub struct AppState {
     pub pool: DbPool,
     pub interval_tx: watch::Sender<u64>,
+    pub scheduler: dyndns::SchedulerHandle,
     pub auth: Arc<auth::AuthManager>,
 }
 
@@ -106,7 +116,19 @@ fn init_dbpool() -> DbPool {
         CONFIG.database_url.as_str(),
         deadpool_diesel::Runtime::Tokio1,
     );
+    // Background tasks write next to the scheduler and the API, so wait for
+    // the lock instead of failing with "database is locked".
+    let busy_timeout = Hook::async_fn(|conn, _| {
+        Box::pin(async move {
+            conn.interact(|conn| diesel::sql_query("PRAGMA busy_timeout = 5000").execute(conn))
+                .await
+                .map_err(|err| HookError::message(err.to_string()))?
+                .map_err(|err| HookError::message(err.to_string()))?;
+            Ok(())
+        })
+    });
     deadpool_diesel::sqlite::Pool::builder(manager)
+        .post_create(busy_timeout)
         .build()
         .unwrap()
 }
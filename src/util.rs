@@ -20,3 +20,34 @@ pub fn random_urlsafe_string(len: usize) -> String {
     OsRng.fill_bytes(&mut bytes);
     URL_SAFE_NO_PAD.encode(bytes)
 }
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986.
+pub fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_encodes_reserved_characters() {
+        assert_eq!(percent_encode("host-1.example_com~"), "host-1.example_com~");
+        assert_eq!(percent_encode("a b&c=d/e?f#g"), "a%20b%26c%3Dd%2Fe%3Ff%23g");
+        assert_eq!(
+            percent_encode("2001:db8::1,::2"),
+            "2001%3Adb8%3A%3A1%2C%3A%3A2"
+        );
+        assert_eq!(percent_encode("pä%ss"), "p%C3%A4%25ss");
+        assert_eq!(percent_encode(""), "");
+    }
+}